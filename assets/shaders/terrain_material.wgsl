@@ -0,0 +1,66 @@
+#import bevy_pbr::{
+    mesh_functions,
+    mesh_view_bindings::globals,
+    forward_io::VertexOutput,
+    view_transformations::position_world_to_clip,
+}
+
+// how long globals.time runs before wrapping back to 0
+@group(2) @binding(100) var<uniform> time_wrap_period: f32;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(5) color: vec4<f32>,
+    // the fog level being faded from and to, when the fade started and how long it lasts
+    @location(8) fog: vec4<f32>,
+};
+
+// 0 is hidden, 1 explored and 2 visible, anything in between is part way through fading from one to the other
+fn apply_fog(colour: vec3<f32>, level: f32) -> vec3<f32> {
+    let hidden = mix(colour, vec3<f32>(0.0), 0.8);
+    let explored = mix(colour, vec3<f32>(0.5), 0.5);
+    if level < 1.0 {
+        return mix(hidden, explored, level);
+    }
+    return mix(explored, colour, level - 1.0);
+}
+
+fn fog_level(fog: vec4<f32>) -> f32 {
+    var elapsed = globals.time - fog.z;
+    if elapsed < 0.0 {
+        elapsed += time_wrap_period;
+    }
+    return mix(fog.x, fog.y, min(elapsed / fog.w, 1.0));
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(vertex.normal, vertex.instance_index);
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4<f32>(vertex.position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+
+#ifdef VERTEX_UVS_A
+    out.uv = vertex.uv;
+#endif
+
+#ifdef VERTEX_COLORS
+    out.color = vec4<f32>(apply_fog(vertex.color.rgb, fog_level(vertex.fog)), vertex.color.a);
+#endif
+
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+
+#ifdef VISIBILITY_RANGE_DITHER
+    out.visibility_range_dither = mesh_functions::get_visibility_range_dither_level(
+        vertex.instance_index, world_from_local[3]);
+#endif
+
+    return out;
+}
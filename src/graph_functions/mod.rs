@@ -1,6 +1,6 @@
-use std::collections::VecDeque;
+use std::{cmp::Ordering, collections::{BinaryHeap, VecDeque}};
 
-use bevy::{ecs::{query::{QueryFilter, QueryEntityError}, entity::Entity, system::Query, component::Component}, utils::hashbrown::HashSet};
+use bevy::{ecs::{query::{QueryFilter, QueryEntityError}, entity::Entity, system::Query, component::Component}, utils::hashbrown::{HashMap, HashSet}};
 
 
 #[derive(Debug)]
@@ -18,9 +18,22 @@ impl From<QueryEntityError> for GraphError {
 
 pub trait GraphVertex : Component{
     fn get_neighbours(&self) -> Vec<Entity>;
+
+    /// The cost of moving from this vertex to the neighbouring vertex `to`.
+    /// Defaults to 1 so that every edge counts as a single step, as in `within_steps`
+    fn edge_cost(&self, _to: &Self) -> f32 {
+        1.0
+    }
+}
+
+pub trait HeuristicVertex : GraphVertex{
+    /// An estimate of the cost of getting from this vertex to `goal`, used to guide `a_star`.
+    /// Should never be more than the true cost, otherwise the path found may not be the cheapest
+    fn heuristic(&self, goal: &Self) -> f32;
 }
 
 
+#[allow(dead_code)]
 pub fn within_steps<V:GraphVertex, T:QueryFilter>(
     start_ent: Entity,
     max_steps: usize,
@@ -67,3 +80,297 @@ pub fn within_steps<V:GraphVertex, T:QueryFilter>(
     }
     Ok(valid)
 }
+
+
+
+/// Dijkstra search from `start_ent` to `goal_ent`, using `GraphVertex::edge_cost` for the edge weights.
+/// Returns the vertices along the cheapest path (including both ends) and its total cost
+#[allow(dead_code)]
+pub fn shortest_path<V:GraphVertex, T:QueryFilter>(
+    start_ent: Entity,
+    goal_ent: Entity,
+    query: &Query<&V, T>
+) -> Result<(Vec<Entity>, f32), GraphError> {
+    let best = best_first_search(start_ent, Some(goal_ent), None, query, |_| 0.0)?;
+    path_to_goal(&best, goal_ent)
+}
+
+/// A* search from `start_ent` to `goal_ent`, guided by `HeuristicVertex::heuristic`.
+/// Gives the same result as `shortest_path` as long as the heuristic never overestimates the remaining cost
+#[allow(dead_code)]
+pub fn a_star<V:HeuristicVertex, T:QueryFilter>(
+    start_ent: Entity,
+    goal_ent: Entity,
+    query: &Query<&V, T>
+) -> Result<(Vec<Entity>, f32), GraphError> {
+    let goal_vert = query.get(goal_ent)?;
+    let best = best_first_search(start_ent, Some(goal_ent), None, query, |vert| vert.heuristic(goal_vert))?;
+    path_to_goal(&best, goal_ent)
+}
+
+/// Every vertex that can be reached from `start_ent` for a total cost of at most `budget`.
+/// Each is returned with its cheapest cost and the vertex it is reached from along that route,
+/// so the path to any of them can be recovered with `rebuild_path` without searching again
+#[allow(dead_code)]
+pub fn within_budget<V:GraphVertex, T:QueryFilter>(
+    start_ent: Entity,
+    budget: f32,
+    query: &Query<&V, T>
+) -> Result<HashMap<Entity, Reached>, GraphError> {
+    best_first_search(start_ent, None, Some(budget), query, |_| 0.0)
+}
+
+
+//shared implementation of every weighted search, dijkstra is just A* with a heuristic of 0.
+//The search stops as soon as `goal_ent` is reached, and never goes past `budget`.
+//Returns the cheapest way found of reaching each vertex, which only includes the goal if there is a path to it
+fn best_first_search<V:GraphVertex, T:QueryFilter>(
+    start_ent: Entity,
+    goal_ent: Option<Entity>,
+    budget: Option<f32>,
+    query: &Query<&V, T>,
+    heuristic: impl Fn(&V) -> f32
+) -> Result<HashMap<Entity, Reached>, GraphError> {
+
+    //make sure both ends are actually part of the graph
+    let start_vert = query.get(start_ent)?;
+    if let Some(goal_ent) = goal_ent {
+        query.get(goal_ent)?;
+    }
+
+    //cheapest known cost to each entity, alongside the entity we reached it from
+    let mut best: HashMap<Entity, Reached> = HashMap::new();
+    best.insert(start_ent, Reached{cost: 0.0, previous: None});
+
+    //entities that have been fully processed
+    let mut done: HashSet<Entity> = HashSet::new();
+
+    //each entry carries the cost of reaching the entity alongside it
+    let mut to_view: BinaryHeap<MinHeapEntry<(f32, Entity)>> = BinaryHeap::from([
+        MinHeapEntry{priority: heuristic(start_vert), item: (0.0, start_ent)}
+    ]);
+
+    while let Some(MinHeapEntry{item: (cost, ent), ..}) = to_view.pop(){
+        //the same entity can be queued several times, only the first (cheapest) one counts
+        if !done.insert(ent){continue;}
+        if goal_ent == Some(ent) {break;}
+
+        let current_vert = query.get(ent)?;
+        for neighbour in current_vert.get_neighbours(){
+            if done.contains(&neighbour){continue;}
+            //neighbours excluded by the query filter are treated as impassable
+            let Ok(vert) = query.get(neighbour) else {continue;};
+
+            let edge_cost = current_vert.edge_cost(vert);
+            if edge_cost < 0.0 || edge_cost.is_nan() {return Err(GraphError::NegativeWeight);}
+
+            //only keep vertices we can actually afford to reach
+            let new_cost = cost + edge_cost;
+            if budget.is_some_and(|budget| new_cost > budget) {continue;}
+
+            if best.get(&neighbour).is_none_or(|old| new_cost < old.cost){
+                best.insert(neighbour, Reached{cost: new_cost, previous: Some(ent)});
+                to_view.push(MinHeapEntry{priority: new_cost + heuristic(vert), item: (new_cost, neighbour)});
+            }
+        }
+    }
+    Ok(best)
+}
+
+//the path and cost to `goal_ent` from a search that was stopped when it reached it
+fn path_to_goal(best: &HashMap<Entity, Reached>, goal_ent: Entity) -> Result<(Vec<Entity>, f32), GraphError> {
+    let cost = best.get(&goal_ent).ok_or(GraphError::NoPath)?.cost;
+    Ok((rebuild_path(best, goal_ent).unwrap_or_default(), cost))
+}
+
+
+/// Follow the predecessors stored in `best` back from `end` to the start of the search.
+/// Returns `None` if `end` was never reached
+#[allow(dead_code)]
+pub fn rebuild_path(best: &HashMap<Entity, Reached>, end: Entity) -> Option<Vec<Entity>> {
+    best.get(&end)?;
+    let mut path = vec![end];
+    let mut current = end;
+    while let Some(&Reached{previous: Some(previous), ..}) = best.get(&current){
+        path.push(previous);
+        current = previous;
+    }
+    path.reverse();
+    Some(path)
+}
+
+
+/// The cheapest known way of reaching a vertex during a weighted search
+#[derive(Debug, Clone, Copy)]
+pub struct Reached{
+    pub cost: f32,
+    pub previous: Option<Entity>
+}
+
+
+/// An entry in a `BinaryHeap` that is popped lowest `priority` first, the reverse of the usual order.
+/// Only the priority is compared, `item` is carried along with it
+pub struct MinHeapEntry<T>{
+    pub priority: f32,
+    pub item: T
+}
+
+impl<T> PartialEq for MinHeapEntry<T>{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for MinHeapEntry<T>{}
+
+impl<T> PartialOrd for MinHeapEntry<T>{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for MinHeapEntry<T>{
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use bevy::ecs::{query::Without, system::SystemState, world::World};
+
+    use super::*;
+
+    #[derive(Component)]
+    struct TestVertex{
+        ent: Entity,
+        //position along a line, used for the heuristic
+        x: f32,
+        edges: Vec<(Entity, f32)>
+    }
+
+    impl GraphVertex for TestVertex{
+        fn get_neighbours(&self) -> Vec<Entity> {
+            self.edges.iter().map(|(ent, _)| *ent).collect()
+        }
+
+        fn edge_cost(&self, to: &Self) -> f32 {
+            self.edges.iter().find(|(ent, _)| *ent == to.ent).unwrap().1
+        }
+    }
+
+    impl HeuristicVertex for TestVertex{
+        fn heuristic(&self, goal: &Self) -> f32 {
+            (self.x - goal.x).abs()
+        }
+    }
+
+    #[derive(Component)]
+    struct Blocked;
+
+    //vertex i sits at x = i, every edge goes both ways
+    fn build_graph(vertices: usize, edges: &[(usize, usize, f32)]) -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        let ents: Vec<Entity> = (0..vertices).map(|_| world.spawn_empty().id()).collect();
+        for (index, &ent) in ents.iter().enumerate(){
+            let vertex_edges = edges.iter().filter_map(|&(a, b, cost)| {
+                if a == index {Some((ents[b], cost))}
+                else if b == index {Some((ents[a], cost))}
+                else {None}
+            }).collect();
+            world.entity_mut(ent).insert(TestVertex{ent, x: index as f32, edges: vertex_edges});
+        }
+        (world, ents)
+    }
+
+    //a direct but expensive edge from 0 to 3, a cheaper route the long way round, and 4 off on its own
+    fn detour_graph() -> (World, Vec<Entity>) {
+        build_graph(5, &[(0, 3, 10.0), (0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0)])
+    }
+
+    #[test]
+    fn cheaper_path_beats_fewer_steps() {
+        let (mut world, ents) = detour_graph();
+        let mut state = SystemState::<Query<&TestVertex>>::new(&mut world);
+        let query = state.get(&world);
+
+        let (path, cost) = shortest_path(ents[0], ents[3], &query).unwrap();
+        assert_eq!(path, vec![ents[0], ents[1], ents[2], ents[3]]);
+        assert_eq!(cost, 3.0);
+    }
+
+    #[test]
+    fn a_star_matches_shortest_path() {
+        let (mut world, ents) = detour_graph();
+        let mut state = SystemState::<Query<&TestVertex>>::new(&mut world);
+        let query = state.get(&world);
+
+        for &goal in ents[..4].iter(){
+            let (_, dijkstra_cost) = shortest_path(ents[0], goal, &query).unwrap();
+            let (path, a_star_cost) = a_star(ents[0], goal, &query).unwrap();
+            assert_eq!(a_star_cost, dijkstra_cost);
+            assert_eq!((path[0], path[path.len() - 1]), (ents[0], goal));
+        }
+    }
+
+    #[test]
+    fn unreachable_goal_has_no_path() {
+        let (mut world, ents) = detour_graph();
+        let mut state = SystemState::<Query<&TestVertex>>::new(&mut world);
+        let query = state.get(&world);
+
+        assert!(matches!(shortest_path(ents[0], ents[4], &query), Err(GraphError::NoPath)));
+        assert!(matches!(a_star(ents[0], ents[4], &query), Err(GraphError::NoPath)));
+    }
+
+    #[test]
+    fn negative_edges_are_rejected() {
+        let (mut world, ents) = build_graph(3, &[(0, 1, 1.0), (1, 2, -1.0)]);
+        let mut state = SystemState::<Query<&TestVertex>>::new(&mut world);
+        let query = state.get(&world);
+
+        assert!(matches!(shortest_path(ents[0], ents[2], &query), Err(GraphError::NegativeWeight)));
+        assert!(matches!(within_budget(ents[0], 5.0, &query), Err(GraphError::NegativeWeight)));
+    }
+
+    #[test]
+    fn filtered_out_vertices_are_impassable() {
+        let (mut world, ents) = detour_graph();
+        world.entity_mut(ents[2]).insert(Blocked);
+        let mut state = SystemState::<Query<&TestVertex, Without<Blocked>>>::new(&mut world);
+        let query = state.get(&world);
+
+        //with the cheap route blocked, only the direct edge is left
+        let (path, cost) = shortest_path(ents[0], ents[3], &query).unwrap();
+        assert_eq!(path, vec![ents[0], ents[3]]);
+        assert_eq!(cost, 10.0);
+        assert!(matches!(shortest_path(ents[0], ents[2], &query), Err(GraphError::InvalidEntity)));
+    }
+
+    #[test]
+    fn within_budget_stops_at_the_budget() {
+        let (mut world, ents) = detour_graph();
+        let mut state = SystemState::<Query<&TestVertex>>::new(&mut world);
+        let query = state.get(&world);
+
+        let reached = within_budget(ents[0], 2.0, &query).unwrap();
+        let mut in_range: Vec<(Entity, f32)> = reached.iter().map(|(ent, reached)| (*ent, reached.cost)).collect();
+        in_range.sort_by(|a, b| a.1.total_cmp(&b.1));
+        assert_eq!(in_range, vec![(ents[0], 0.0), (ents[1], 1.0), (ents[2], 2.0)]);
+
+        assert_eq!(rebuild_path(&reached, ents[2]), Some(vec![ents[0], ents[1], ents[2]]));
+        assert_eq!(rebuild_path(&reached, ents[3]), None);
+    }
+
+    #[test]
+    fn within_steps_counts_steps_not_cost() {
+        let (mut world, ents) = detour_graph();
+        let mut state = SystemState::<Query<&TestVertex>>::new(&mut world);
+        let query = state.get(&world);
+
+        let mut reached = within_steps(ents[0], 1, &query).unwrap();
+        reached.sort();
+        assert_eq!(reached, vec![(ents[0], 0), (ents[1], 1), (ents[3], 1)]);
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::{asset::{io::Reader, Asset, AssetLoader, AsyncReadExt, LoadContext}, color::Color, ecs::component::Component, reflect::TypePath};
+use serde::Deserialize;
+use thiserror::Error;
+
+
+/// What kind of terrain a tile is, decided during terrain generation from the `BiomeRules`
+#[derive(Component, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Biome{
+    Ocean,
+    Lake,
+    Tundra,
+    Swamp,
+    Grassland,
+    Taiga,
+    Desert,
+    Forest,
+    Mountain,
+    Snow,
+    Crag
+}
+
+
+/// The properties of a tile that come from the biome rule it matched
+#[derive(Component, Clone, Debug)]
+pub struct BiomeProperties{
+    pub colour: Color,
+    pub movement_cost: f32,
+    /// Free form labels for gameplay systems to check, eg "water" or "forest"
+    pub tags: Vec<String>
+}
+
+impl BiomeProperties{
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+
+/// The list of rules used to pick each tile's biome, loaded from a `.biomes.ron` file.
+/// Rules are checked in order and the first one whose conditions all hold is used
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct BiomeRules{
+    pub rules: Vec<BiomeRule>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BiomeRule{
+    pub biome: Biome,
+    #[serde(default)]
+    pub height: Bounds<f32>,
+    #[serde(default)]
+    pub moisture: Bounds<f32>,
+    #[serde(default)]
+    pub temperature: Bounds<f32>,
+    /// The number of neighbouring tiles this one is at least as high as
+    #[serde(default)]
+    pub higher_than: Bounds<usize>,
+    /// If set, whether the tile has to be (or not be) part of a `Lake`
+    #[serde(default)]
+    pub lake: Option<bool>,
+    /// If set, whether the tile has to be (or not be) below the sea level
+    #[serde(default)]
+    pub sea: Option<bool>,
+    /// Linear rgb
+    pub colour: (f32, f32, f32),
+    /// Must be at least 1 so that `HeuristicVertex` stays admissible
+    pub movement_cost: f32,
+    #[serde(default)]
+    pub tags: Vec<String>
+}
+
+/// An inclusive range where either end can be left out
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Bounds<T>{
+    pub min: Option<T>,
+    pub max: Option<T>
+}
+
+impl<T> Default for Bounds<T>{
+    fn default() -> Self {
+        Self {min: None, max: None}
+    }
+}
+
+impl<T: PartialOrd> Bounds<T>{
+    pub fn contains(&self, value: T) -> bool {
+        self.min.as_ref().is_none_or(|min| &value >= min) && self.max.as_ref().is_none_or(|max| &value <= max)
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!((&self.min, &self.max), (Some(min), Some(max)) if min > max)
+    }
+
+    fn is_unbounded(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+}
+
+/// The terrain values of a tile that the biome rules are checked against
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainSample{
+    pub height: f32,
+    pub higher_than: usize,
+    pub moisture: f32,
+    pub temperature: f32,
+    pub lake: bool,
+    pub below_sea_level: bool
+}
+
+impl BiomeRules{
+
+    /// The first rule matching a tile with the given terrain values, if any
+    pub fn classify(&self, sample: &TerrainSample) -> Option<&BiomeRule> {
+        self.rules.iter().find(|rule| {
+            rule.lake.is_none_or(|wanted| wanted == sample.lake)
+            && rule.sea.is_none_or(|wanted| wanted == sample.below_sea_level)
+            && rule.height.contains(sample.height)
+            && rule.higher_than.contains(sample.higher_than)
+            && rule.moisture.contains(sample.moisture)
+            && rule.temperature.contains(sample.temperature)
+        })
+    }
+
+    //catch mistakes that would otherwise only show up as odd looking terrain
+    fn validate(&self) -> Result<(), BiomeRulesError> {
+        let Some(last) = self.rules.last() else {return Err(BiomeRulesError::NoRules);};
+        if !last.is_catch_all() {
+            return Err(BiomeRulesError::NoCatchAll(last.biome));
+        }
+        for (index, rule) in self.rules.iter().enumerate(){
+            let invalid = |reason: &str| BiomeRulesError::InvalidRule{index, biome: rule.biome, reason: reason.to_string()};
+
+            if rule.height.is_empty() || rule.moisture.is_empty() || rule.temperature.is_empty() || rule.higher_than.is_empty() {
+                return Err(invalid("a condition has its min above its max"));
+            }
+            if rule.movement_cost.is_nan() || rule.movement_cost < 1.0 {
+                return Err(invalid("movement_cost must be at least 1"));
+            }
+            let (r, g, b) = rule.colour;
+            if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+                return Err(invalid("colour components must be between 0 and 1"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BiomeRule{
+    fn is_catch_all(&self) -> bool {
+        self.height.is_unbounded() && self.moisture.is_unbounded()
+        && self.temperature.is_unbounded() && self.higher_than.is_unbounded()
+        && self.lake.is_none() && self.sea.is_none()
+    }
+
+    pub fn properties(&self) -> BiomeProperties {
+        let (r, g, b) = self.colour;
+        BiomeProperties{
+            colour: Color::linear_rgb(r, g, b),
+            movement_cost: self.movement_cost,
+            tags: self.tags.clone()
+        }
+    }
+}
+
+
+#[derive(Default)]
+pub struct BiomeRulesLoader;
+
+#[derive(Debug, Error)]
+pub enum BiomeRulesError{
+    #[error("could not read biome rules: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse biome rules: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("biome rules file has no rules")]
+    NoRules,
+    #[error("the last biome rule ({0:?}) has conditions, it should have none so that every tile gets a biome")]
+    NoCatchAll(Biome),
+    #[error("biome rule {index} ({biome:?}) is invalid: {reason}")]
+    InvalidRule{index: usize, biome: Biome, reason: String}
+}
+
+impl AssetLoader for BiomeRulesLoader{
+    type Asset = BiomeRules;
+    type Settings = ();
+    type Error = BiomeRulesError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let rules: BiomeRules = ron::de::from_bytes(&bytes)?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["biomes.ron"]
+    }
+}
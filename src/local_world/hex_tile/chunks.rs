@@ -0,0 +1,122 @@
+use bevy::{app::{Plugin, PostUpdate}, asset::{Assets, Handle}, ecs::{component::Component, entity::Entity, query::{Changed, Or}, system::{Commands, Query, Res, ResMut, Resource}}, hierarchy::BuildChildren, math::Vec3, pbr::MaterialMeshBundle, prelude::IntoSystemConfigs, render::{mesh::Mesh, primitives::Aabb, view::VisibilitySystems}, state::state::OnEnter, utils::hashbrown::{HashMap, HashSet}};
+
+use crate::GameState;
+
+use super::{hex_materials::FogTransition, hex_mesh::{column_depth, ColumnMeshBuilder, OutlineMeshBuilder, TerrainMaterials}, HexCoord, HexLayout, HexPositionMap, HexSpawnSet, HexTile, TileColour, TileHeight};
+
+
+/// Chunks are `CHUNK_SIZE` by `CHUNK_SIZE` tiles, along q and r
+const CHUNK_SIZE: i32 = 8;
+
+
+pub struct ChunkPlugin;
+impl Plugin for ChunkPlugin{
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app
+        .init_resource::<ChunkMap>()
+        .add_systems(OnEnter(GameState::LocalWorld), spawn_chunks.after(HexSpawnSet))
+        //after everything in Update has had the chance to change the tiles,
+        //and before the bounds are worked out so that the rebuilt chunks are culled the same frame
+        .add_systems(PostUpdate, rebuild_chunks.before(VisibilitySystems::CalculateBounds));
+    }
+}
+
+
+/// Which chunk a tile belongs to, chunks are parallelograms in axial coordinates
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ChunkCoord{
+    pub q: i32,
+    pub r: i32
+}
+
+impl ChunkCoord{
+    pub fn containing(coord: HexCoord) -> Self {
+        Self {q: coord.q.div_euclid(CHUNK_SIZE), r: coord.r.div_euclid(CHUNK_SIZE)}
+    }
+}
+
+/// A group of tiles drawn together as a single mesh, which is rebuilt whenever any of them changes.
+/// The `HexTile` entities themselves have no meshes
+#[derive(Component)]
+pub struct TerrainChunk{
+    pub tiles: Vec<Entity>,
+    //the child entity drawing the tile outlines
+    outline: Entity
+}
+
+#[derive(Resource, Default)]
+pub struct ChunkMap{
+    pub map: HashMap<ChunkCoord, Entity>
+}
+
+
+//the meshes start out empty, they are filled in by rebuild_chunks once the tiles have their heights and colours
+fn spawn_chunks(
+    mut commands: Commands,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    tile_map: Res<HexPositionMap>,
+    materials: Res<TerrainMaterials>
+) {
+    let mut chunk_tiles: HashMap<ChunkCoord, Vec<Entity>> = HashMap::new();
+    for (&coord, &ent) in tile_map.map.iter(){
+        chunk_tiles.entry(ChunkCoord::containing(coord)).or_default().push(ent);
+    }
+
+    for (chunk_coord, tiles) in chunk_tiles{
+        let outline = commands.spawn(MaterialMeshBundle{
+            mesh: meshes.add(OutlineMeshBuilder::default().build()),
+            material: materials.outline_material.clone(),
+            ..Default::default()
+        }).id();
+        let chunk = commands.spawn((
+            MaterialMeshBundle{
+                mesh: meshes.add(ColumnMeshBuilder::default().build()),
+                material: materials.chunk_material.clone(),
+                ..Default::default()
+            },
+            TerrainChunk{tiles, outline}
+        )).add_child(outline).id();
+        chunk_map.map.insert(chunk_coord, chunk);
+    }
+}
+
+
+//only the chunks with a tile that changed since the last run are rebuilt
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn rebuild_chunks(
+    mut commands: Commands,
+    changed_tiles: Query<&HexTile, Or<(Changed<TileColour>, Changed<TileHeight>, Changed<FogTransition>)>>,
+    tiles_q: Query<(&HexTile, &TileHeight, &TileColour, &FogTransition)>,
+    chunks: Query<&TerrainChunk>,
+    mesh_handles: Query<&Handle<Mesh>>,
+    chunk_map: Res<ChunkMap>,
+    layout: Res<HexLayout>,
+    mut meshes: ResMut<Assets<Mesh>>
+) {
+    let to_rebuild: HashSet<ChunkCoord> = changed_tiles.iter()
+        .map(|tile| ChunkCoord::containing(tile.position))
+        .collect();
+
+    for chunk_coord in to_rebuild{
+        let Some(&chunk_ent) = chunk_map.map.get(&chunk_coord) else {continue;};
+        let Ok(chunk) = chunks.get(chunk_ent) else {continue;};
+
+        let mut columns = ColumnMeshBuilder::default();
+        let mut outlines = OutlineMeshBuilder::default();
+        for (tile, height, colour, fog) in chunk.tiles.iter().filter_map(|&ent| tiles_q.get(ent).ok()){
+            let xz_pos = layout.coord_to_xz(tile.position);
+            let top = Vec3::new(xz_pos.x, height.0, xz_pos.y);
+            let depth = column_depth(height.0, &layout);
+            columns.add_column(&layout, top, depth, colour.0, fog.vertex_data());
+            outlines.add_outline(&layout, top, depth);
+        }
+        for (ent, mesh) in [(chunk_ent, columns.build()), (chunk.outline, outlines.build())]{
+            if let Ok(handle) = mesh_handles.get(ent){
+                meshes.insert(handle, mesh);
+            }
+            //the bounds used for culling are only worked out when missing, so they have to be cleared to follow the new mesh
+            commands.entity(ent).remove::<Aabb>();
+        }
+    }
+}
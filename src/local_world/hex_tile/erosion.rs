@@ -0,0 +1,121 @@
+use bevy::{ecs::{entity::Entity, system::{Query, Res}}, utils::hashbrown::HashMap};
+
+use crate::random_gens::{ErosionParams, TerrainParams, TileRng, WorldSeed};
+
+use super::{HexCoord, HexTile, TileHeight};
+
+
+//the furthest a single drop of rain can run before it is dropped
+const MAX_DROP_STEPS: usize = 64;
+
+
+//runs straight after `add_elevation`, wearing down the freshly sampled heights and writing them back to the `TileHeight`s.
+//Tiles are always worked through in the same order and each one draws from its own `TileRng`,
+//so the result only depends on the seed and the settings
+pub(super) fn erode_terrain(
+    mut tiles_q: Query<(Entity, &HexTile, &mut TileHeight)>,
+    params: Res<TerrainParams>,
+    seed: Res<WorldSeed>
+) {
+    let erosion = &params.erosion;
+    if erosion.hydraulic_iterations == 0 && erosion.thermal_iterations == 0 {return;}
+
+    let mut tiles: Vec<(Entity, HexCoord)> = tiles_q.iter().map(|(ent, tile, _)| (ent, tile.position)).collect();
+    tiles.sort_by_key(|(_, coord)| (coord.q, coord.r));
+    let index: HashMap<Entity, usize> = tiles.iter().enumerate().map(|(i, (ent, _))| (*ent, i)).collect();
+
+    let mut field = HeightField{
+        heights: tiles.iter().map(|(ent, _)| tiles_q.get(*ent).unwrap().2.0).collect(),
+        neighbours: tiles.iter().map(|(ent, _)| {
+            tiles_q.get(*ent).unwrap().1.neighbours.iter().filter_map(|other| index.get(other).copied()).collect()
+        }).collect()
+    };
+
+    let mut rngs: Vec<TileRng> = tiles.iter().map(|(_, coord)| TileRng::new(*seed, *coord, "hydraulic erosion")).collect();
+    for _ in 0..erosion.hydraulic_iterations{
+        for (start, rng) in rngs.iter_mut().enumerate(){
+            field.run_drop(start, rng.range_f32(0.5, 1.5), erosion);
+        }
+    }
+    for _ in 0..erosion.thermal_iterations{
+        field.crumble(erosion);
+    }
+
+    for ((ent, _), height) in tiles.iter().zip(field.heights){
+        tiles_q.get_mut(*ent).unwrap().2.0 = height;
+    }
+}
+
+
+//the tile heights, with each tile's neighbours as indices into the same list
+struct HeightField{
+    heights: Vec<f32>,
+    neighbours: Vec<Vec<usize>>
+}
+
+impl HeightField{
+
+    fn lowest_neighbour(&self, tile: usize) -> Option<usize> {
+        self.neighbours[tile].iter().copied()
+            .min_by(|&a, &b| self.heights[a].total_cmp(&self.heights[b]))
+    }
+
+    //follow a drop of rain downhill from `start`, picking up soil on the way down
+    //and leaving it behind where the drop slows down or gets stuck in a hollow
+    fn run_drop(&mut self, start: usize, mut water: f32, erosion: &ErosionParams) {
+        let mut tile = start;
+        let mut sediment: f32 = 0.0;
+
+        for _ in 0..MAX_DROP_STEPS{
+            let Some(next) = self.lowest_neighbour(tile) else {break;};
+            let slope = self.heights[tile] - self.heights[next];
+            if slope <= 0.0 {
+                //stuck in a hollow, fill it up to at most the height of its lowest neighbour
+                let fill = sediment.min(-slope);
+                self.heights[tile] += fill;
+                //then share out whatever is left between the two, now that they're level, so no soil is lost
+                let leftover = 0.5 * (sediment - fill);
+                self.heights[tile] += leftover;
+                self.heights[next] += leftover;
+                return;
+            }
+
+            let capacity = slope * water * erosion.sediment_capacity;
+            if sediment > capacity {
+                let deposit = (sediment - capacity) * erosion.deposition_rate;
+                self.heights[tile] += deposit;
+                sediment -= deposit;
+            }
+            else {
+                //never dig below the tile downhill, or the drop would make its own hollow
+                let eroded = ((capacity - sediment) * erosion.erosion_rate).min(slope);
+                self.heights[tile] -= eroded;
+                sediment += eroded;
+            }
+
+            water *= 1.0 - erosion.evaporation;
+            tile = next;
+        }
+        //whatever is left over settles where the drop ran out
+        self.heights[tile] += sediment;
+    }
+
+    //move soil from every slope steeper than the talus onto the tile below it.
+    //The changes are all worked out before any are applied, so the order tiles are visited in doesn't matter
+    fn crumble(&mut self, erosion: &ErosionParams) {
+        let mut changes = vec![0.0; self.heights.len()];
+        for (tile, neighbours) in self.neighbours.iter().enumerate(){
+            for &other in neighbours.iter(){
+                let excess = self.heights[tile] - self.heights[other] - erosion.talus;
+                if excess <= 0.0 {continue;}
+                //split between all six sides, so a peak can't lose more than it has over its neighbours
+                let moved = 0.5 * erosion.thermal_rate * excess / 6.0;
+                changes[tile] -= moved;
+                changes[other] += moved;
+            }
+        }
+        for (height, change) in self.heights.iter_mut().zip(changes){
+            *height += change;
+        }
+    }
+}
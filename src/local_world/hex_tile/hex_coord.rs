@@ -0,0 +1,236 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+
+/// A position on the hex grid, stored in axial coordinates.
+/// The third cube coordinate is implied by q + r + s = 0 and available through `s` and `cube`.
+/// How these map onto the world is decided by the `HexLayout`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct HexCoord{
+    pub q: i32,
+    pub r: i32
+}
+
+impl HexCoord{
+    pub const ZERO: HexCoord = HexCoord::new(0, 0);
+
+    /// The offsets to each of the six neighbours.
+    /// Each direction is one 60° rotation (see `rotate`) on from the previous one
+    pub const DIRECTIONS: [HexCoord; 6] = [
+        HexCoord::new(1, 0),
+        HexCoord::new(1, -1),
+        HexCoord::new(0, -1),
+        HexCoord::new(-1, 0),
+        HexCoord::new(-1, 1),
+        HexCoord::new(0, 1),
+    ];
+
+    pub const fn new(q: i32, r: i32) -> Self {
+        Self {q, r}
+    }
+
+    pub fn from_cube(q: i32, r: i32, s: i32) -> Self {
+        debug_assert!(q + r + s == 0, "cube coordinates must sum to 0");
+        Self {q, r}
+    }
+
+    /// The coordinate containing the fractional axial position (q, r),
+    /// found by rounding in cube space and fixing up whichever component moved the most
+    pub fn round(q: f32, r: f32) -> Self {
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        Self {q: rq as i32, r: rr as i32}
+    }
+
+    /// Offset coordinates (i, j) where the odd columns are shifted half a tile in the positive j direction
+    pub fn from_odd_q(i: i32, j: i32) -> Self {
+        //i - (i & 1) is always even, so the division is exact for negative columns too
+        Self {q: i, r: j - (i - (i & 1)) / 2}
+    }
+
+    pub fn to_odd_q(self) -> (i32, i32) {
+        (self.q, self.r + (self.q - (self.q & 1)) / 2)
+    }
+
+    /// Offset coordinates (i, j) where the odd rows are shifted half a tile in the positive i direction
+    pub fn from_odd_r(i: i32, j: i32) -> Self {
+        Self {q: i - (j - (j & 1)) / 2, r: j}
+    }
+
+    pub fn to_odd_r(self) -> (i32, i32) {
+        (self.q + (self.r - (self.r & 1)) / 2, self.r)
+    }
+
+    pub fn s(self) -> i32 {
+        -self.q - self.r
+    }
+
+    pub fn cube(self) -> (i32, i32, i32) {
+        (self.q, self.r, self.s())
+    }
+
+    /// The number of steps from the origin to this coordinate
+    pub fn length(self) -> i32 {
+        (self.q.abs() + self.r.abs() + self.s().abs()) / 2
+    }
+
+    /// The number of steps between two coordinates
+    pub fn distance(self, other: HexCoord) -> i32 {
+        (self - other).length()
+    }
+
+    /// Rotates about the origin by `steps` lots of 60°, negative steps rotate the other way
+    #[allow(dead_code)]
+    pub fn rotate(self, steps: i32) -> Self {
+        let (mut q, mut r, mut s) = self.cube();
+        for _ in 0..steps.rem_euclid(6){
+            (q, r, s) = (-s, -q, -r);
+        }
+        Self::from_cube(q, r, s)
+    }
+
+    /// Rotates about `centre` by `steps` lots of 60°
+    #[allow(dead_code)]
+    pub fn rotate_around(self, centre: HexCoord, steps: i32) -> Self {
+        (self - centre).rotate(steps) + centre
+    }
+
+    /// Reflects across the axis of constant q through the origin, swapping r and s
+    #[allow(dead_code)]
+    pub fn reflect_q(self) -> Self {
+        Self::from_cube(self.q, self.s(), self.r)
+    }
+
+    /// Reflects across the axis of constant r through the origin, swapping q and s
+    #[allow(dead_code)]
+    pub fn reflect_r(self) -> Self {
+        Self::from_cube(self.s(), self.r, self.q)
+    }
+
+    /// Reflects across the axis of constant s through the origin, swapping q and r
+    #[allow(dead_code)]
+    pub fn reflect_s(self) -> Self {
+        Self::from_cube(self.r, self.q, self.s())
+    }
+
+    /// The neighbour in the given direction, an index into `DIRECTIONS` (wrapping around after 6)
+    #[allow(dead_code)]
+    pub fn neighbour(self, direction: usize) -> Self {
+        self + Self::DIRECTIONS[direction % 6]
+    }
+
+    pub fn neighbours(self) -> [HexCoord; 6] {
+        Self::DIRECTIONS.map(|dir| self + dir)
+    }
+}
+
+
+impl Add for HexCoord{
+    type Output = HexCoord;
+
+    fn add(self, rhs: HexCoord) -> Self::Output {
+        HexCoord::new(self.q + rhs.q, self.r + rhs.r)
+    }
+}
+
+impl AddAssign for HexCoord{
+    fn add_assign(&mut self, rhs: HexCoord) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for HexCoord{
+    type Output = HexCoord;
+
+    fn sub(self, rhs: HexCoord) -> Self::Output {
+        HexCoord::new(self.q - rhs.q, self.r - rhs.r)
+    }
+}
+
+impl SubAssign for HexCoord{
+    fn sub_assign(&mut self, rhs: HexCoord) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for HexCoord{
+    type Output = HexCoord;
+
+    fn neg(self) -> Self::Output {
+        HexCoord::new(-self.q, -self.r)
+    }
+}
+
+impl Mul<i32> for HexCoord{
+    type Output = HexCoord;
+
+    fn mul(self, rhs: i32) -> Self::Output {
+        HexCoord::new(self.q * rhs, self.r * rhs)
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn odd_q_round_trips() {
+        for i in -5..=5{
+            for j in -5..=5{
+                assert_eq!(HexCoord::from_odd_q(i, j).to_odd_q(), (i, j), "odd-q ({}, {})", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn odd_r_round_trips() {
+        for i in -5..=5{
+            for j in -5..=5{
+                assert_eq!(HexCoord::from_odd_r(i, j).to_odd_r(), (i, j), "odd-r ({}, {})", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn odd_q_negative_columns_are_shifted() {
+        //odd columns sit half a tile further along j, negative ones included
+        assert_eq!(HexCoord::from_odd_q(-1, 0), HexCoord::new(-1, 1));
+        assert_eq!(HexCoord::from_odd_q(-2, 0), HexCoord::new(-2, 1));
+        assert_eq!(HexCoord::from_odd_q(-3, 0), HexCoord::new(-3, 2));
+    }
+
+    #[test]
+    fn round_picks_the_nearest_tile() {
+        assert_eq!(HexCoord::round(0.0, 0.0), HexCoord::ZERO);
+        assert_eq!(HexCoord::round(2.1, -0.9), HexCoord::new(2, -1));
+        assert_eq!(HexCoord::round(-1.4, 0.3), HexCoord::new(-1, 0));
+        //rounding each axial component on its own would give (1, 1), which is two steps away
+        assert_eq!(HexCoord::round(0.7, 0.6), HexCoord::new(1, 0));
+    }
+
+    #[test]
+    fn round_keeps_cube_coordinates_valid() {
+        for step in -20..=20{
+            let (q, r) = (step as f32 * 0.37, step as f32 * -0.53);
+            let rounded = HexCoord::round(q, r);
+            let (cq, cr, cs) = rounded.cube();
+            assert_eq!(cq + cr + cs, 0);
+            assert!((rounded.q as f32 - q).abs() <= 1.0 && (rounded.r as f32 - r).abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn distance_counts_steps() {
+        assert_eq!(HexCoord::new(2, -1).distance(HexCoord::new(-1, 2)), 3);
+        for (index, dir) in HexCoord::DIRECTIONS.iter().enumerate(){
+            assert_eq!(dir.length(), 1);
+            assert_eq!(dir.rotate(1), HexCoord::DIRECTIONS[(index + 1) % 6]);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::f32::consts::PI;
+
+use bevy::{ecs::system::Resource, math::{Vec2, Vec3}};
+
+use super::HexCoord;
+
+
+const SQRT_3: f32 = 1.732050807568877293527446341505872367_f32;
+
+
+/// Which way up the hexagons sit in the xz plane
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum HexOrientation{
+    /// Flat sides facing ±z, so tiles line up in straight columns along z
+    #[default]
+    Flat,
+    /// Corners facing ±z, so tiles line up in straight rows along x
+    #[allow(dead_code)]
+    Pointy
+}
+
+
+/// Describes how the hex grid is laid out in the world.
+/// The tile meshes, world/coordinate conversions and offset coordinates are all derived from this,
+/// so changing it keeps the geometry and picking in agreement
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct HexLayout{
+    pub orientation: HexOrientation,
+    /// Distance from the centre of a tile to its corners
+    pub size: f32,
+    /// The height the bottom of every tile's column reaches down to, so the terrain reads as one solid block
+    pub base_height: f32
+}
+
+impl Default for HexLayout{
+    fn default() -> Self {
+        //tiles 1 unit across their flat sides
+        Self {orientation: HexOrientation::Flat, size: 1.0 / SQRT_3, base_height: -2.5}
+    }
+}
+
+impl HexLayout{
+
+    /// The world x and z position of the centre of a tile
+    pub fn coord_to_xz(&self, coord: HexCoord) -> Vec2 {
+        let (q, r) = (coord.q as f32, coord.r as f32);
+        let unscaled = match self.orientation{
+            HexOrientation::Flat => Vec2::new(1.5 * q, SQRT_3 * (0.5 * q + r)),
+            HexOrientation::Pointy => Vec2::new(SQRT_3 * (q + 0.5 * r), 1.5 * r),
+        };
+        self.size * unscaled
+    }
+
+    /// The tile containing the world point (x, z), the inverse of `coord_to_xz`
+    pub fn xz_to_coord(&self, x: f32, z: f32) -> HexCoord {
+        let (x, z) = (x / self.size, z / self.size);
+        match self.orientation{
+            HexOrientation::Flat => HexCoord::round(2.0 / 3.0 * x, -x / 3.0 + SQRT_3 / 3.0 * z),
+            HexOrientation::Pointy => HexCoord::round(SQRT_3 / 3.0 * x - z / 3.0, 2.0 / 3.0 * z),
+        }
+    }
+
+    /// Converts offset coordinates, where every other column (flat) or row (pointy)
+    /// is shifted by half a tile, into a `HexCoord`
+    pub fn offset_to_coord(&self, i: i32, j: i32) -> HexCoord {
+        match self.orientation{
+            HexOrientation::Flat => HexCoord::from_odd_q(i, j),
+            HexOrientation::Pointy => HexCoord::from_odd_r(i, j),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn coord_to_offset(&self, coord: HexCoord) -> (i32, i32) {
+        match self.orientation{
+            HexOrientation::Flat => coord.to_odd_q(),
+            HexOrientation::Pointy => coord.to_odd_r(),
+        }
+    }
+
+    /// The position of corner `index` of a tile relative to its centre, going round from +x towards +z.
+    /// Side `index` of the mesh runs between corners `index` and `index + 1`
+    pub fn corner(&self, index: usize) -> Vec3 {
+        let start_angle = match self.orientation{
+            HexOrientation::Flat => 0.0,
+            HexOrientation::Pointy => PI / 6.0,
+        };
+        let (sin, cos) = (start_angle + index as f32 * PI / 3.0).sin_cos();
+        Vec3::new(self.size * cos, 0.0, self.size * sin)
+    }
+}
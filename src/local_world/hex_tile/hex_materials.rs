@@ -1,16 +1,14 @@
-use std::time::Duration;
-
 use bevy::{
-    app::{Plugin, Update}, asset::{Asset, Assets, Handle}, color::{Color, LinearRgba, Mix}, ecs::{component::Component, system::{Query, Res, ResMut}}, pbr::{
-        Material, MaterialPipeline, MaterialPipelineKey, MaterialPlugin, StandardMaterial
-    }, prelude::IntoSystemConfigs, reflect::TypePath, render::{
-        mesh::MeshVertexBufferLayoutRef, render_resource::{
-            AsBindGroup, PolygonMode, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError
+    app::Plugin, asset::Asset, color::LinearRgba, ecs::component::Component, pbr::{
+        ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline, Material, MaterialPipeline, MaterialPipelineKey, MaterialPlugin, StandardMaterial
+    }, reflect::{Reflect, TypePath}, render::{
+        mesh::{MeshVertexAttribute, MeshVertexBufferLayoutRef}, render_resource::{
+            AsBindGroup, PolygonMode, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError, VertexFormat
         }
-    }, state::condition::in_state, time::{self, Time, Timer, Virtual}
+    }, time::{Time, Virtual}
 };
 
-use crate::GameState;
+use super::TileExploredState;
 
 
 pub struct HexMaterialsPlugin;
@@ -19,65 +17,99 @@ impl Plugin for HexMaterialsPlugin{
     fn build(&self, app: &mut bevy::prelude::App) {
         app
         .add_plugins(MaterialPlugin::<OutlineMaterial>::default())
-        .add_systems(Update, update_colour_transitions.run_if(in_state(GameState::LocalWorld)));
+        .add_plugins(MaterialPlugin::<TerrainMaterial>::default());
     }
 }
 
 
-fn update_colour_transitions(
-    mut handles: Query<(&Handle<StandardMaterial>, &mut ColourTransition)>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    time: Res<Time<Virtual>>
-){
-    let time_step = time.delta();
+//how long a tile takes to fade from one state of the fog of war to another
+const FOG_TRANSITION_SECS: f32 = 2.0;
+//where the fog attribute is bound in the vertex shader, after all of the locations bevy uses for its own attributes
+const FOG_SHADER_LOCATION: u32 = 8;
 
-    for (handle, mut transition) in handles.iter_mut(){
-        //update the colours
-        match materials.get_mut(handle.id()){
-            Some(material) => material.base_color = transition.get_interpolated_colour(),
-            None => continue,
-        }
-        //tick the timers
-        transition.tick(time_step)
-    }
+
+/// Per vertex fog of war, see `FogTransition::vertex_data` for the layout
+pub const ATTRIBUTE_FOG: MeshVertexAttribute = MeshVertexAttribute::new("Vertex_Fog", 733172589, VertexFormat::Float32x4);
+
+
+/// How a tile is fading between two states of the fog of war.
+/// The fade itself is worked out in the terrain shader from the time it started,
+/// so a tile's chunk only needs rebuilding when a new transition begins
+#[derive(Component, Clone, Copy, Debug)]
+pub struct FogTransition{
+    from: f32,
+    to: f32,
+    //seconds since startup, wrapped the same way as the time given to shaders
+    started: f32,
+    duration: f32
 }
 
+impl FogTransition{
 
+    /// A tile sitting in `state` without any fade
+    pub fn settled(state: &TileExploredState) -> Self {
+        let level = state.fog_level();
+        Self {from: level, to: level, started: 0.0, duration: FOG_TRANSITION_SECS}
+    }
 
+    /// Starts fading to `state` from wherever the current fade has got to
+    pub fn fade_to(&mut self, state: &TileExploredState, time: &Time<Virtual>) {
+        let now = time.elapsed_seconds_wrapped();
+        *self = Self {
+            from: self.level_at(now, time.wrap_period().as_secs_f32()),
+            to: state.fog_level(),
+            started: now,
+            duration: FOG_TRANSITION_SECS
+        };
+    }
 
-#[derive(Component)]
-pub struct ColourTransition{
-    start_colour: Color,
-    end_colour: Color,
-    timer: Timer
-}
+    /// Whether the tile is, or is fading into, `state`
+    pub fn is_heading_to(&self, state: &TileExploredState) -> bool {
+        self.to == state.fog_level()
+    }
 
-impl Default for ColourTransition{
-    fn default() -> Self {
-        Self { 
-            start_colour: Color::linear_rgba(0.5, 0.5, 0.5, 1.0), //Grey
-            end_colour: Color::linear_rgba(0.0, 1.0, 0.0, 1.0), //green
-            timer: Timer::from_seconds(2.0, time::TimerMode::Once) 
-        }
+    //the same as the terrain shader
+    fn level_at(&self, now: f32, wrap_period: f32) -> f32 {
+        let elapsed = (now - self.started).rem_euclid(wrap_period);
+        self.from + (self.to - self.from) * (elapsed / self.duration).min(1.0)
+    }
+
+    /// The value of `ATTRIBUTE_FOG` for the tile's vertices: the fog level it's fading from and to,
+    /// the time the fade started and how long it lasts
+    pub fn vertex_data(&self) -> [f32; 4] {
+        [self.from, self.to, self.started, self.duration]
     }
 }
 
-impl ColourTransition{
 
-    pub fn new(start_col: Color, end_col: Color, secs: f32) -> Self {
-        Self {
-            start_colour: start_col, 
-            end_colour: end_col, 
-            timer: Timer::from_seconds(secs, time::TimerMode::Once) 
-        }
-    }
+/// The material shared by every chunk of terrain. The tiles' colours come from the vertex colours,
+/// with the fog of war applied on top in the vertex shader from `ATTRIBUTE_FOG`
+pub type TerrainMaterial = ExtendedMaterial<StandardMaterial, FogOfWarExtension>;
+
+#[derive(AsBindGroup, Debug, Clone, Asset, Reflect)]
+pub struct FogOfWarExtension{
+    /// How long the time given to shaders runs before wrapping back to 0, taken from `Time::wrap_period`
+    /// so that the shader and `FogTransition` agree on fades that start just before it wraps
+    #[uniform(100)]
+    pub time_wrap_period: f32
+}
 
-    fn tick(&mut self, delta: Duration){
-        self.timer.tick(delta);
+impl MaterialExtension for FogOfWarExtension{
+
+    fn vertex_shader() -> ShaderRef {
+        "shaders/terrain_material.wgsl".into()
     }
 
-    pub fn get_interpolated_colour(&self) -> Color {
-        self.start_colour.mix(&self.end_colour, self.timer.fraction())
+    //the depth and shadow passes use the standard shaders and just ignore the extra attribute
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let fog_layout = layout.0.get_layout(&[ATTRIBUTE_FOG.at_shader_location(FOG_SHADER_LOCATION)])?;
+        descriptor.vertex.buffers[0].attributes.extend(fog_layout.attributes);
+        Ok(())
     }
 }
 
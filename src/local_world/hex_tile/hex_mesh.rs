@@ -1,6 +1,6 @@
-use bevy::{app::{Plugin, PreStartup}, asset::{Assets, Handle}, color::LinearRgba, ecs::system::{Commands, ResMut, Resource}, render::{mesh::{Indices, Mesh}, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology}};
+use bevy::{app::{Plugin, PreStartup}, asset::{Assets, Handle}, color::{Color, ColorToComponents, LinearRgba}, ecs::system::{Commands, Res, ResMut, Resource}, math::Vec3, pbr::{ExtendedMaterial, StandardMaterial}, render::{mesh::{Indices, Mesh}, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology}, time::{Time, Virtual}};
 
-use super::hex_materials::OutlineMaterial;
+use super::{hex_materials::{FogOfWarExtension, OutlineMaterial, TerrainMaterial, ATTRIBUTE_FOG}, HexLayout};
 
 
 pub struct HexMeshPlugin;
@@ -11,286 +11,140 @@ impl Plugin for HexMeshPlugin{
 }
 
 
+//how deep the column is for a tile that is at or below the base height
+const MIN_COLUMN_DEPTH: f32 = 0.1;
+
 
 fn create_handles(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
     mut outline_materials: ResMut<Assets<OutlineMaterial>>,
+    time: Res<Time<Virtual>>
 ) {
-    commands.insert_resource(HexagonMeshHandles{
-        hex_mesh: meshes.add(create_hex_mesh()),
-        outline_mesh: meshes.add(create_outline_mesh()),
+    commands.insert_resource(TerrainMaterials{
+        //white so that the vertex colours come through unchanged
+        chunk_material: materials.add(ExtendedMaterial{
+            base: StandardMaterial::from(Color::WHITE),
+            extension: FogOfWarExtension{time_wrap_period: time.wrap_period().as_secs_f32()}
+        }),
         outline_material: outline_materials.add(OutlineMaterial{outline_colour: LinearRgba::new(1.0, 1.0, 1.0, 0.2)})
     });
 }
 
+/// The materials shared by every chunk of terrain, the tile colours and fog of war themselves are stored in the meshes
 #[derive(Resource)]
-pub struct HexagonMeshHandles{
-    pub hex_mesh: Handle<Mesh>,
-    pub outline_mesh: Handle<Mesh>,
+pub struct TerrainMaterials{
+    pub chunk_material: Handle<TerrainMaterial>,
     pub outline_material: Handle<OutlineMaterial>
 }
 
 
-/// We assume a hexagonal grid with hexagons with flat sides on top and bottom
-/// This results in the lines of constant i looking zig-zaggy
-/// and lines of constant j to be straight
-pub fn x_from_coord(i: i32, _j: i32) -> f32 {
-    i as f32 * FRAC_1_SQRT_3 * 1.5
-}
-
-pub fn z_from_coord(i: i32, j: i32) -> f32 {
-    j as f32 + if i % 2 != 0 {0.5} else {0.0}
+/// How far down from its top face a tile's column goes to reach `HexLayout::base_height`
+pub fn column_depth(height: f32, layout: &HexLayout) -> f32 {
+    (height - layout.base_height).max(MIN_COLUMN_DEPTH)
 }
 
 
-fn create_hex_mesh() -> Mesh {
-    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all())
-    .with_inserted_attribute(
-        Mesh::ATTRIBUTE_POSITION,
-        Vec::from(VERTEX_POSITIONS)
-    )
-    .with_inserted_attribute(
-        Mesh::ATTRIBUTE_UV_0,
-        Vec::from(VERTEX_UVS)
-    )
-    .with_inserted_attribute(
-        Mesh::ATTRIBUTE_NORMAL,
-        Vec::from(VERTEX_NORMALS)
-        )
-    .with_inserted_indices(Indices::U32(Vec::from(VERTEX_INDICES)))
-}
-
-fn create_outline_mesh() -> Mesh {
-    Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD)
-    .with_inserted_attribute(
-        Mesh::ATTRIBUTE_POSITION, 
-        Vec::from(OUTLINE_POSITIONS)
-    )
-    .with_inserted_indices(Indices::U32(Vec::from(OUTLINE_INDICES)))
+/// Vertex data for many tile columns merged into a single mesh, coloured per tile with vertex colours
+/// and with each tile's `FogTransition` in `ATTRIBUTE_FOG`
+#[derive(Default)]
+pub struct ColumnMeshBuilder{
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<[f32; 2]>,
+    colours: Vec<[f32; 4]>,
+    fog: Vec<[f32; 4]>,
+    indices: Vec<u32>
 }
 
+impl ColumnMeshBuilder{
+
+    /// Adds a hexagonal column with its top face centred on `top`, reaching `depth` down
+    pub fn add_column(&mut self, layout: &HexLayout, top: Vec3, depth: f32, colour: Color, fog: [f32; 4]) {
+        let corners: Vec<Vec3> = (0..6).map(|index| top + layout.corner(index)).collect();
+        let down = Vec3::new(0.0, -depth, 0.0);
+        let colour = colour.to_linear().to_f32_array();
+        let start = self.positions.len() as u32;
+
+        //uvs for the top and bottom faces map the hexagon onto the unit square
+        let face_uv = |corner: Vec3| [0.5 + 0.5 * (corner.x - top.x) / layout.size, 0.5 + 0.5 * (corner.z - top.z) / layout.size];
+
+        //top face, vertices 0-5
+        for &corner in corners.iter(){
+            self.positions.push(corner);
+            self.normals.push(Vec3::Y);
+            self.uvs.push(face_uv(corner));
+        }
+        for i in 1..5{
+            self.indices.extend([start, start + i + 1, start + i]);
+        }
+
+        //bottom face, vertices 6-11
+        for &corner in corners.iter(){
+            self.positions.push(corner + down);
+            self.normals.push(Vec3::NEG_Y);
+            self.uvs.push(face_uv(corner));
+        }
+        for i in 1..5{
+            self.indices.extend([start + 6, start + 6 + i, start + 6 + i + 1]);
+        }
+
+        //side faces, 4 vertices each
+        for side in 0..6{
+            let (side_start, side_end) = (corners[side], corners[(side + 1) % 6]);
+            let normal = (side_start + side_end - 2.0 * top).normalize();
+            let first = self.positions.len() as u32;
+
+            self.positions.extend([side_start, side_end, side_start + down, side_end + down]);
+            self.normals.extend([normal; 4]);
+            self.uvs.extend([[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]]);
+            self.indices.extend([first, first + 1, first + 2, first + 1, first + 3, first + 2]);
+        }
+
+        self.colours.resize(self.positions.len(), colour);
+        self.fog.resize(self.positions.len(), fog);
+    }
 
-const FRAC_1_SQRT_3: f32 = 0.577350269189625764509148780501957456_f32;
-const SQRT_3: f32 = 1.732050807568877293527446341505872367_f32;
-
-const MINOR_RADIUS : f32 = 0.5;
-//const MAJOR_RADIUS : f32 = FRAC_1_SQRT_3; // = 2/sqrt(3) * minor_radius
-
-const HEIGHT : f32 = 2.0;
-
-const X : f32 = MINOR_RADIUS * FRAC_1_SQRT_3;
-const Z : f32 = MINOR_RADIUS;
-
-
-pub const VERTEX_POSITIONS : [[f32; 3]; 36] = [
-            [2.0*X, 0.0, 0.0],      //v0    0
-            [X, 0.0, Z],            //v1    1
-            [-X, 0.0, Z],           //v2    2
-            [-2.0*X, 0.0, 0.0],     //v3    3
-            [-X, 0.0, -Z],          //v4    4
-            [X, 0.0, -Z],           //v5    5
-
-            //Bottom Face Vertices
-            [2.0*X, -HEIGHT, 0.0],  //v6    6
-            [X, -HEIGHT, Z],        //v7    7
-            [-X, -HEIGHT, Z],       //v8    8
-            [-2.0*X, -HEIGHT, 0.0], //v9    9
-            [-X, -HEIGHT, -Z],      //v10   10
-            [X, -HEIGHT, -Z],       //v11   11
-
-            //Side faces vertices
-            //1
-            [2.0*X, 0.0, 0.0],      //v0    12
-            [X, 0.0, Z],            //v1    13
-            [2.0*X, -HEIGHT, 0.0],  //v6    14
-            [X, -HEIGHT, Z],        //v7    15
-            //2
-            [X, 0.0, Z],            //v1    16
-            [-X, 0.0, Z],           //v2    17
-            [X, -HEIGHT, Z],        //v7    18
-            [-X, -HEIGHT, Z],       //v8    19
-            //3
-            [-X, 0.0, Z],           //v2    20
-            [-2.0*X, 0.0, 0.0],     //v3    21
-            [-X, -HEIGHT, Z],       //v8    22
-            [-2.0*X, -HEIGHT, 0.0], //v9    23
-            //4
-            [-2.0*X, 0.0, 0.0],     //v3    24
-            [-X, 0.0, -Z],          //v4    25
-            [-2.0*X, -HEIGHT, 0.0], //v9    26
-            [-X, -HEIGHT, -Z],      //v10   27
-            //5
-            [-X, 0.0, -Z],          //v4    28
-            [X, 0.0, -Z],           //v5    29
-            [-X, -HEIGHT, -Z],      //v10   30
-            [X, -HEIGHT, -Z],       //v11   31
-            //6
-            [X, 0.0, -Z],           //v5    32
-            [2.0*X, 0.0, 0.0],      //v0    33
-            [X, -HEIGHT, -Z],       //v11   34
-            [2.0*X, -HEIGHT, 0.0]   //v6    35
-];
-
-pub const VERTEX_UVS : [[f32; 2]; 36] = [
-    [0.0, 0.5],                     //0
-    [0.25*FRAC_1_SQRT_3, 0.75],     //1
-    [0.75*FRAC_1_SQRT_3, 0.75],     //2
-    [FRAC_1_SQRT_3, 0.5],           //3
-    [0.75*FRAC_1_SQRT_3, 0.25],     //4
-    [0.25*FRAC_1_SQRT_3, 0.25],     //5
-
-    //bottom face    todo->flip order?
-    [0.0, 0.5],                     //6
-    [0.25*FRAC_1_SQRT_3, 0.75],     //7
-    [0.75*FRAC_1_SQRT_3, 0.75],     //8
-    [FRAC_1_SQRT_3, 0.5],           //9
-    [0.75*FRAC_1_SQRT_3, 0.25],     //10
-    [0.25*FRAC_1_SQRT_3, 0.25],     //11
-
-    //sides           todo->flip order?
-    //1
-    [1.0-0.5*FRAC_1_SQRT_3, 0.0],   //12
-    [1.0, 0.0],                     //13
-    [1.0-0.5*FRAC_1_SQRT_3, 1.0],   //14
-    [1.0, 1.0],                     //15
-    //2
-    [1.0-0.5*FRAC_1_SQRT_3, 0.0],   //16
-    [1.0, 0.0],                     //17
-    [1.0-0.5*FRAC_1_SQRT_3, 1.0],   //18
-    [1.0, 1.0],                     //19
-    //3
-    [1.0-0.5*FRAC_1_SQRT_3, 0.0],   //20
-    [1.0, 0.0],                     //21
-    [1.0-0.5*FRAC_1_SQRT_3, 1.0],   //22
-    [1.0, 1.0],                     //23
-    //4
-    [1.0-0.5*FRAC_1_SQRT_3, 0.0],   //24
-    [1.0, 0.0],                     //25
-    [1.0-0.5*FRAC_1_SQRT_3, 1.0],   //26
-    [1.0, 1.0],                     //27
-    //5
-    [1.0-0.5*FRAC_1_SQRT_3, 0.0],   //28
-    [1.0, 0.0],                     //29
-    [1.0-0.5*FRAC_1_SQRT_3, 1.0],   //30
-    [1.0, 1.0],                     //31
-    //6
-    [1.0-0.5*FRAC_1_SQRT_3, 0.0],   //32
-    [1.0, 0.0],                     //33
-    [1.0-0.5*FRAC_1_SQRT_3, 1.0],   //34
-    [1.0, 1.0],                     //35
-];
-
-pub const VERTEX_NORMALS : [[f32; 3]; 36] = [
-    [0.0, 1.0, 0.0],        //v0    0
-    [0.0, 1.0, 0.0],        //v1    1
-    [0.0, 1.0, 0.0],        //v2    2
-    [0.0, 1.0, 0.0],        //v3    3
-    [0.0, 1.0, 0.0],        //v4    4
-    [0.0, 1.0, 0.0],        //v5    5
-
-    //Bottom Face Vertices
-    [0.0, -1.0, 0.0],       //v6    6
-    [0.0, -1.0, 0.0],       //v7    7
-    [0.0, -1.0, 0.0],       //v8    8
-    [0.0, -1.0, 0.0],       //v9    9
-    [0.0, -1.0, 0.0],       //v10   10
-    [0.0, -1.0, 0.0],       //v11   11
-
-    //Side faces vertices
-    //1
-    [0.5 * SQRT_3, 0.0, 0.5],   //v0    12
-    [0.5 * SQRT_3, 0.0, 0.5],   //v1    13
-    [0.5 * SQRT_3, 0.0, 0.5],   //v6    14
-    [0.5 * SQRT_3, 0.0, 0.5],   //v7    15
-    //2
-    [0.0, 0.0, 1.0],            //v1    16
-    [0.0, 0.0, 1.0],            //v2    17
-    [0.0, 0.0, 1.0],            //v7    18
-    [0.0, 0.0, 1.0],            //v8    19
-    //3
-    [-0.5 * SQRT_3, 0.0, 0.5],  //v2    20
-    [-0.5 * SQRT_3, 0.0, 0.5],  //v3    21
-    [-0.5 * SQRT_3, 0.0, 0.5],  //v8    22
-    [-0.5 * SQRT_3, 0.0, 0.5],  //v9    23
-    //4
-    [-0.5 * SQRT_3, 0.0, -0.5], //v3    24
-    [-0.5 * SQRT_3, 0.0, -0.5], //v4    25
-    [-0.5 * SQRT_3, 0.0, -0.5], //v9    26
-    [-0.5 * SQRT_3, 0.0, -0.5], //v10   27
-    //5
-    [0.0, 0.0, -1.0],           //v4    28
-    [0.0, 0.0, -1.0],           //v5    29
-    [0.0, 0.0, -1.0],           //v10   30
-    [0.0, 0.0, -1.0],           //v11   31
-    //6
-    [0.5 * SQRT_3, 0.0, -0.5],  //v5    32
-    [0.5 * SQRT_3, 0.0, -0.5],  //v0    33
-    [0.5 * SQRT_3, 0.0, -0.5],  //v11   34
-    [0.5 * SQRT_3, 0.0, -0.5]   //v6    35
-];
-
-pub const VERTEX_INDICES : [u32; 60]= [
-        0, 2, 1,
-        0, 3, 2,
-        0, 5, 3,
-        3, 5, 4,
-
-        // Bottom Face
-        8, 6, 7,
-        9, 6, 8,
-        11, 6, 9,
-        11, 9, 10,
-
-        // Side Faces
-        //1
-        12, 13, 14,
-        13, 15, 14,
-
-        //2
-        16, 17, 18,
-        17, 19, 18,
-
-        //3
-        20, 21, 22,
-        21, 23, 22,
+    pub fn build(self) -> Mesh {
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, self.colours)
+        .with_inserted_attribute(ATTRIBUTE_FOG, self.fog)
+        .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
 
-        //4
-        24, 25, 26,
-        25, 27, 26,
 
-        //5
-        28, 29, 30,
-        29, 31, 30,
+/// The edges of many tile columns merged into a single line mesh
+#[derive(Default)]
+pub struct OutlineMeshBuilder{
+    positions: Vec<Vec3>,
+    indices: Vec<u32>
+}
 
-        //6
-        32, 33, 34,
-        33, 35, 34,
-];
+impl OutlineMeshBuilder{
 
-pub const OUTLINE_POSITIONS : [[f32; 3]; 12] = [
-    [2.0*X, 0.0, 0.0],      //v0
-    [X, 0.0, Z],            //v1
-    [-X, 0.0, Z],           //v2
-    [-2.0*X, 0.0, 0.0],     //v3
-    [-X, 0.0, -Z],          //v4
-    [X, 0.0, -Z],           //v5
+    /// Adds the outline of a column with its top face centred on `top`, reaching `depth` down
+    pub fn add_outline(&mut self, layout: &HexLayout, top: Vec3, depth: f32) {
+        let down = Vec3::new(0.0, -depth, 0.0);
+        let start = self.positions.len() as u32;
 
-    //Bottom Face Vertice
-    [2.0*X, -HEIGHT, 0.0],  //v6
-    [X, -HEIGHT, Z],        //v7
-    [-X, -HEIGHT, Z],       //v8
-    [-2.0*X, -HEIGHT, 0.0], //v9
-    [-X, -HEIGHT, -Z],      //v10
-    [X, -HEIGHT, -Z],       //v11
-];
+        //top corners are 0-5, bottom corners 6-11
+        self.positions.extend((0..6).map(|index| top + layout.corner(index)));
+        self.positions.extend((0..6).map(|index| top + layout.corner(index) + down));
 
-pub const OUTLINE_INDICES : [u32; 36] = [
-    //Top face lines
-    0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 0,
-    //Bottom face lines
-    6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 6,
-    //Side face lines
-    0, 6, 1, 7, 2, 8, 3, 9, 4, 10, 5, 11
-];
+        for i in 0..6{
+            let next = (i + 1) % 6;
+            //top edge, bottom edge, then the vertical edge
+            self.indices.extend([i, next, 6 + i, 6 + next, i, 6 + i].map(|index| start + index));
+        }
+    }
 
+    pub fn build(self) -> Mesh {
+        Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::BinaryHeap;
+
+use bevy::{ecs::{component::Component, entity::Entity, query::Changed, system::{Commands, Query, ResMut, Resource}}, utils::hashbrown::HashMap};
+
+use crate::graph_functions::MinHeapEntry;
+
+use super::{HexTile, TileHeight};
+
+
+/// Marks a tile at the bottom of a closed basin, which has been filled with water up to where it spills over
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Lake{
+    /// The height of the lake's surface, the same for every tile in the lake
+    pub water_level: f32
+}
+
+
+/// How water runs across the map once every basin has been filled.
+/// Every tile drains somewhere, except the ones on the edge of the map where the water runs off
+#[derive(Resource, Default)]
+pub struct Drainage{
+    /// The neighbour each tile's water flows into, with lakes filled in this is never uphill
+    pub downstream: HashMap<Entity, Entity>,
+    /// Every tile from the lowest surface to the highest, each coming after the tile it drains into
+    pub order: Vec<Entity>
+}
+
+
+//whenever the tile heights change, flood the map inwards from its edges, always continuing from the lowest point
+//reached so far. A tile that is lower than the one it is reached from must be in a basin, so it is raised to
+//that height, which is where the basin spills over
+pub(super) fn fill_depressions(
+    mut commands: Commands,
+    mut drainage: ResMut<Drainage>,
+    changed: Query<(), Changed<TileHeight>>,
+    tiles_q: Query<(Entity, &HexTile, &TileHeight)>
+) {
+    if changed.is_empty() {return;}
+
+    let heights: HashMap<Entity, f32> = tiles_q.iter()
+        .map(|(ent, _, height)| (ent, height.0))
+        .collect();
+
+    let mut surface: HashMap<Entity, f32> = HashMap::new();
+    let mut reached_from: HashMap<Entity, Entity> = HashMap::new();
+    let mut order: Vec<Entity> = Vec::with_capacity(heights.len());
+
+    //tiles missing a neighbour are on the edge of the map
+    //each entry is the surface level the flood has reached the tile at
+    let mut to_flood: BinaryHeap<MinHeapEntry<Entity>> = tiles_q.iter()
+        .filter(|(_, tile, _)| tile.neighbours.len() < 6)
+        .map(|(ent, _, height)| MinHeapEntry{priority: height.0, item: ent})
+        .collect();
+    for entry in to_flood.iter(){
+        surface.insert(entry.item, entry.priority);
+    }
+
+    //each tile is only queued once, when it's first reached
+    while let Some(MinHeapEntry{priority: level, item: ent}) = to_flood.pop(){
+        order.push(ent);
+
+        let Ok((_, tile, _)) = tiles_q.get(ent) else {continue;};
+        for &neighbour in tile.neighbours.iter(){
+            if surface.contains_key(&neighbour) {continue;}
+            let Some(&height) = heights.get(&neighbour) else {continue;};
+
+            let neighbour_level = height.max(level);
+            surface.insert(neighbour, neighbour_level);
+            reached_from.insert(neighbour, ent);
+            to_flood.push(MinHeapEntry{priority: neighbour_level, item: neighbour});
+        }
+    }
+
+    //take the steepest way down where there is one, and otherwise the way the flood came in,
+    //which leads across the flat surface of a lake to where it spills out
+    let downstream = tiles_q.iter().filter_map(|(ent, tile, _)| {
+        let own_level = *surface.get(&ent)?;
+        let steepest = tile.neighbours.iter()
+            .filter_map(|other| surface.get(other).map(|&level| (*other, level)))
+            .filter(|&(_, level)| level < own_level)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(lowest, _)| lowest);
+        steepest.or_else(|| reached_from.get(&ent).copied()).map(|down| (ent, down))
+    }).collect();
+
+    for (ent, height) in heights.iter(){
+        match surface.get(ent){
+            Some(&level) if level > *height => commands.entity(*ent).insert(Lake{water_level: level}),
+            _ => commands.entity(*ent).remove::<Lake>(),
+        };
+    }
+
+    *drainage = Drainage{downstream, order};
+}
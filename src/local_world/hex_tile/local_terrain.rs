@@ -1,64 +1,133 @@
-use bevy::{app::{Plugin, Update}, asset::{Assets, Handle}, color::Color, hierarchy::Children, pbr::StandardMaterial, prelude::{IntoSystemConfigs, Query, Res, ResMut, With}, state::state::OnEnter, transform::components::Transform};
+use bevy::{app::{Plugin, Startup, Update}, asset::{AssetApp, AssetEvent, AssetServer, Assets, Handle}, ecs::system::SystemParam, prelude::{in_state, resource_changed, Changed, Commands, DetectChanges, DetectChangesMut, Entity, EventReader, Has, IntoSystemConfigs, Query, Res, Resource, With, Without}, state::state::OnEnter};
 
-use crate::{random_gens::HeightmapNoise, GameState};
+use crate::{random_gens::{HeightmapNoise, MoistureNoise, TemperatureNoise, TerrainParams}, GameState};
 
-use super::{hex_neighbours, HexSpawnSet, HexTile};
+use super::{biome::{BiomeRules, BiomeRulesLoader, TerrainSample}, erosion::erode_terrain, lakes::{fill_depressions, Drainage, Lake}, rivers::generate_rivers, Biome, HexLayout, HexSpawnSet, HexTile, TileColour, TileHeight};
+
+
+const BIOME_RULES_PATH: &str = "default.biomes.ron";
 
 
 pub struct TerrainPlugin;
 
 impl Plugin for TerrainPlugin{
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(OnEnter(GameState::LocalWorld), (add_elevation, determine_biomes).after(HexSpawnSet));
+        app
+        .init_asset::<BiomeRules>()
+        .init_asset_loader::<BiomeRulesLoader>()
+        .init_resource::<Drainage>()
+        .add_systems(Startup, load_biome_rules)
+        .add_systems(OnEnter(GameState::LocalWorld), (add_elevation, erode_terrain, terrace_heights).chain().after(HexSpawnSet))
+        .add_systems(Update, (
+            (add_elevation, erode_terrain, terrace_heights).chain().run_if(resource_changed::<HeightmapNoise>),
+            fill_depressions,
+            determine_biomes,
+            generate_rivers
+        ).chain().run_if(in_state(GameState::LocalWorld)));
     }
 }
 
 
 
 
+//the only place the height noise is sampled, everything else reads the `TileHeight` this fills in
 fn add_elevation(
-    mut tiles_q: Query<&mut Transform, With<HexTile>>,
-    heightmap_noise: Res<HeightmapNoise>
+    mut tiles_q: Query<(&HexTile, &mut TileHeight)>,
+    heightmap_noise: Res<HeightmapNoise>,
+    layout: Res<HexLayout>
 ){
-    for mut transform in tiles_q.iter_mut(){
-        transform.translation.y = heightmap_noise.height_at_xz(transform.translation.x, transform.translation.z);
+    for (tile, mut height) in tiles_q.iter_mut(){
+        height.0 = heightmap_noise.height_at_coord(tile.position, &layout);
     }
 }
 
-fn determine_biomes(
-    tiles_q: Query<(&HexTile, &Children)>,
-    colours: Query<&Handle<StandardMaterial>>,
-    heightmap_noise: Res<HeightmapNoise>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+//snaps the heights to whole levels when terracing is turned on, otherwise every tile is on level 0
+fn terrace_heights(
+    mut tiles_q: Query<(&mut HexTile, &mut TileHeight)>,
+    params: Res<TerrainParams>
 ) {
-    for (tile, children) in tiles_q.iter(){
-        let self_height = heightmap_noise.height_at_coord(tile.position.0, tile.position.1);
-        let mut higher_than = 0;
-
-        for other_pos in hex_neighbours(&tile.position){
-            higher_than += if self_height >= heightmap_noise.height_at_coord(other_pos.0, other_pos.1) {1} else {0};
-        }
-
-        //iterate over the children, filter-mapping to the assosciated Handle<StandardMaterial>
-        //there should be exactly one of these, but for robustness we flatmap to convert into the material
-        let mat = match children.iter()
-        .filter_map(|child|{colours.get(*child).ok()})
-        .next().and_then(|handle| materials.get_mut(handle)){
-            Some(material) => material,
-            None => continue,
+    for (mut tile, mut height) in tiles_q.iter_mut(){
+        let level = match params.terrace_step{
+            Some(step) => {
+                let level = (height.0 / step).round() as i32;
+                height.0 = level as f32 * step;
+                level
+            },
+            None => 0,
         };
+        HexTile::set_if_neq(&mut tile, |tile| &mut tile.level, level);
+    }
+}
+
+//everything about the terrain that the biomes are decided from
+#[derive(SystemParam)]
+struct TerrainInputs<'w, 's>{
+    heights: Query<'w, 's, &'static TileHeight>,
+    changed_heights: Query<'w, 's, (), Changed<TileHeight>>,
+    params: Res<'w, TerrainParams>,
+    moisture: Res<'w, MoistureNoise>,
+    temperature: Res<'w, TemperatureNoise>
+}
 
-        mat.base_color = match self_height {
-            height if height > 0.5 && higher_than > 3 => Color::linear_rgba(0.3, 0.3, 0.3, 1.0),
-            height if height > 0.5 && higher_than == 0 => Color::linear_rgba(1.0, 1.0, 1.0, 1.0),
-            height if height > 0.5 => Color::linear_rgba(0.5, 0.5, 0.5, 1.0),
-            height if height > 0.2 => Color::linear_rgba(0.0, 0.5, 0.0, 1.0),
-            height if height > -0.2 && higher_than == 0 => Color::linear_rgba(0.0, 0.0, 1.0, 1.0),
-            height if height > -0.2 => Color::linear_rgba(0.5, 0.75, 0.1, 1.0),
-            height if height <= -0.2 => Color::linear_rgba(0.5, 0.8, 1.0, 1.0),
-            _ => Color::linear_rgba(1.0, 0.0, 1.0, 1.0)
-        }.into();
-        
-        
+fn load_biome_rules(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.insert_resource(BiomeRulesHandle(asset_server.load(BIOME_RULES_PATH)));
+}
+
+//runs every frame, but only does anything once the rules have loaded and there are tiles without a biome,
+//or when the rules file, terrain or lakes have changed and everything needs reclassifying
+#[allow(clippy::too_many_arguments)]
+fn determine_biomes(
+    mut commands: Commands,
+    mut tiles_q: Query<(Entity, &mut HexTile, &mut TileColour, Has<Biome>)>,
+    lakes: Query<&Lake>,
+    unassigned: Query<(), (With<HexTile>, Without<Biome>)>,
+    terrain: TerrainInputs,
+    layout: Res<HexLayout>,
+    drainage: Res<Drainage>,
+    rules_handle: Res<BiomeRulesHandle>,
+    rules_assets: Res<Assets<BiomeRules>>,
+    mut rules_events: EventReader<AssetEvent<BiomeRules>>,
+) {
+    let rules_changed = rules_events.read().any(|event| event.is_modified(&rules_handle.0));
+    let reclassify_all = rules_changed || !terrain.changed_heights.is_empty() || drainage.is_changed();
+    if unassigned.is_empty() && !reclassify_all {return;}
+    let Some(rules) = rules_assets.get(&rules_handle.0) else {return;};
+
+    for (ent, mut tile, mut colour, has_biome) in tiles_q.iter_mut(){
+        if has_biome && !reclassify_all {continue;}
+
+        let Ok(&TileHeight(self_height)) = terrain.heights.get(ent) else {continue;};
+        let lake = lakes.get(ent).ok();
+        let xz_pos = layout.coord_to_xz(tile.position);
+        let moisture = terrain.moisture.moisture_at_xz(xz_pos.x, xz_pos.y);
+        //a lake's climate is the one at its surface rather than at the bottom
+        let surface_height = lake.map_or(self_height, |lake| lake.water_level);
+        let temperature = terrain.temperature.temperature_at_xz(xz_pos.x, xz_pos.y, surface_height);
+
+        let higher_than = tile.neighbours.iter()
+            .filter_map(|&other| terrain.heights.get(other).ok())
+            .filter(|other| self_height >= other.0)
+            .count();
+
+        //the rules are validated to end in a catch-all, so this always finds something
+        let Some(rule) = rules.classify(&TerrainSample{
+            height: self_height,
+            higher_than,
+            moisture,
+            temperature,
+            lake: lake.is_some(),
+            below_sea_level: self_height < terrain.params.sea_level
+        }) else {continue;};
+        let properties = rule.properties();
+        HexTile::set_if_neq(&mut tile, |tile| &mut tile.movement_cost, properties.movement_cost);
+        colour.set_if_neq(TileColour(properties.colour));
+        commands.entity(ent).insert((rule.biome, properties));
     }
-}
\ No newline at end of file
+}
+
+
+#[derive(Resource)]
+struct BiomeRulesHandle(Handle<BiomeRules>);
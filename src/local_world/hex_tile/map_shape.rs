@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::Resource, utils::hashbrown::{HashMap, HashSet}};
+
+use super::{hex_spiral, HexCoord, HexLayout};
+
+
+/// The footprint of the map, used to decide which tiles get spawned.
+/// The player starts at the origin, so a mask should always include it
+#[derive(Resource, Clone, Debug)]
+pub enum MapShape{
+    /// All tiles within `radius` steps of the origin
+    Hexagon{radius: i32},
+    /// A `width` by `height` block of offset coordinates (see `HexLayout::offset_to_coord`), centred on the origin
+    #[allow(dead_code)]
+    Rectangle{width: i32, height: i32},
+    /// A rhombus with `width` tiles along q and `height` tiles along r, centred on the origin
+    #[allow(dead_code)]
+    Parallelogram{width: i32, height: i32},
+    /// A triangle with sides of `size` tiles, with one corner at the origin
+    #[allow(dead_code)]
+    Triangle{size: i32},
+    /// An arbitrary shape, given as a `width` wide grid in offset coordinates
+    /// (row by row, centred on the origin) where only the tiles marked true are spawned
+    Mask{width: i32, cells: Vec<bool>}
+}
+
+impl Default for MapShape{
+    fn default() -> Self {
+        Self::Hexagon{radius: 25}
+    }
+}
+
+impl MapShape{
+
+    /// Builds a `Mask` from rows of text, where `#` marks a tile and anything else is left empty.
+    /// Shorter rows are padded out to the longest one
+    #[allow(dead_code)]
+    pub fn mask_from_rows(rows: &[&str]) -> Self {
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        let cells = rows.iter().flat_map(|row| {
+            row.chars().map(|c| c == '#').chain(std::iter::repeat(false)).take(width)
+        }).collect();
+        Self::Mask{width: width as i32, cells}
+    }
+
+    /// All the coordinates covered by this shape, the layout deciding how offset coordinates are arranged
+    pub fn coords(&self, layout: &HexLayout) -> Vec<HexCoord> {
+        match self{
+            MapShape::Hexagon{radius} => hex_spiral(HexCoord::ZERO, *radius).collect(),
+            MapShape::Rectangle{width, height} => {
+                let (min_i, min_j) = (-width / 2, -height / 2);
+                (min_i..min_i + width).flat_map(|i| {
+                    (min_j..min_j + height).map(move |j| layout.offset_to_coord(i, j))
+                }).collect()
+            },
+            MapShape::Parallelogram{width, height} => {
+                let (min_q, min_r) = (-width / 2, -height / 2);
+                (min_q..min_q + width).flat_map(|q| {
+                    (min_r..min_r + height).map(move |r| HexCoord::new(q, r))
+                }).collect()
+            },
+            MapShape::Triangle{size} => {
+                let size = *size;
+                (0..size).flat_map(|q| {
+                    (0..size - q).map(move |r| HexCoord::new(q, r))
+                }).collect()
+            },
+            MapShape::Mask{width, cells} => {
+                let width = (*width).max(1);
+                let height = (cells.len() as i32 + width - 1) / width;
+                let (min_i, min_j) = (-width / 2, -height / 2);
+                cells.iter().enumerate()
+                    .filter(|(_, &include)| include)
+                    .map(|(index, _)| {
+                        let index = index as i32;
+                        layout.offset_to_coord(min_i + index % width, min_j + index / width)
+                    }).collect()
+            },
+        }
+    }
+
+    /// How many steps each coordinate in the shape is from the nearest one outside it,
+    /// so the tiles around the edge are 1 and the distance grows going inwards
+    pub fn edge_distances(&self, layout: &HexLayout) -> HashMap<HexCoord, i32> {
+        let coords: HashSet<HexCoord> = self.coords(layout).into_iter().collect();
+        let mut distances: HashMap<HexCoord, i32> = HashMap::new();
+        let mut to_visit: VecDeque<HexCoord> = VecDeque::new();
+
+        for &coord in coords.iter(){
+            if coord.neighbours().iter().any(|other| !coords.contains(other)){
+                distances.insert(coord, 1);
+                to_visit.push_back(coord);
+            }
+        }
+        //breadth first, so every coordinate is reached by the shortest route in from the edge
+        while let Some(coord) = to_visit.pop_front(){
+            let distance = distances[&coord];
+            for other in coord.neighbours(){
+                if coords.contains(&other) && !distances.contains_key(&other){
+                    distances.insert(other, distance + 1);
+                    to_visit.push_back(other);
+                }
+            }
+        }
+        distances
+    }
+}
@@ -1,44 +1,59 @@
+mod biome;
+mod chunks;
+mod erosion;
+mod hex_coord;
+mod hex_layout;
 mod hex_mesh;
 mod hex_materials;
+mod lakes;
 mod local_terrain;
+mod map_shape;
+mod rivers;
 
 use bevy::{
-    app::
-        Plugin, 
-        asset::Assets, 
+    app::{Plugin, Update}, 
         color::Color, 
-        ecs::{component::Component, entity::Entity, event::{Event, EventReader, EventWriter}, 
+        ecs::{component::Component, entity::Entity, event::{Event, EventReader, EventWriter}, query::Changed, 
         schedule::{apply_deferred, IntoSystemConfigs}, 
         system::{Commands, Query, Res, ResMut, Resource}}, 
-        hierarchy::BuildChildren, math::Vec3, 
-        pbr::{MaterialMeshBundle, StandardMaterial}, 
-        prelude::{SpatialBundle, SystemSet}, 
+        math::Vec3, 
+        prelude::{DetectChanges, DetectChangesMut, Mut, Ref, SpatialBundle, SystemSet}, 
         state::state::OnEnter, 
         transform::components::Transform, 
         utils::hashbrown::HashMap
 };
 
-use bevy_mod_raycast::deferred::RaycastMesh;
-
-
 use hex_materials::HexMaterialsPlugin;
-pub use hex_materials::ColourTransition;
+pub use hex_materials::FogTransition;
 use local_terrain::TerrainPlugin;
-use crate::{graph_functions::GraphVertex, GameState};
-use self::hex_mesh::{HexMeshPlugin, HexagonMeshHandles};
-pub use self::hex_mesh::{x_from_coord, z_from_coord};
-
+use crate::{graph_functions::{GraphVertex, HeuristicVertex}, GameState};
+use self::chunks::ChunkPlugin;
+use self::hex_mesh::HexMeshPlugin;
+pub use self::biome::{Biome, BiomeProperties};
+pub use self::hex_coord::HexCoord;
+pub use self::hex_layout::{HexLayout, HexOrientation};
+pub use self::map_shape::MapShape;
+
+
+#[derive(Default)]
+pub struct HexPlugin{
+    pub shape: MapShape,
+    pub layout: HexLayout
+}
 
-pub struct HexPlugin;
 impl Plugin for HexPlugin{
     fn build(&self, app: &mut bevy::prelude::App) {
         app
+        .insert_resource(self.shape.clone())
+        .insert_resource(self.layout)
         .add_plugins(HexMaterialsPlugin)
         .add_plugins(HexMeshPlugin)
+        .add_plugins(ChunkPlugin)
         .add_plugins(TerrainPlugin)
         .init_resource::<HexagonsToUpdate>()
         .init_resource::<HexPositionMap>()
         .add_event::<SpawnHexEvent>()
+        .add_systems(Update, (sync_tile_transforms, snap_to_tiles).chain())
         .add_systems(OnEnter(GameState::LocalWorld), 
     (
                 setup_hexes, 
@@ -69,53 +84,91 @@ fn apply_neighbour_changes(
     }
 }
 
-pub fn hex_neighbours(pos: &(i32, i32)) -> Vec<(i32,i32)> {
-    // 0 1
-    //2 . 3
-    // 4 5
-    const POSSIBLE_NEIGHBOURS : [(i32, i32); 6] = [
-        (1, 0), (1, -1), 
-        (0, 1), (0, -1), 
-        (-1, 0), (-1, -1)
-    ];
-    let mult = if pos.0 % 2 == 0 {1} else {-1};
-    POSSIBLE_NEIGHBOURS.into_iter().map(|(x, y)| (pos.0 + mult * x, pos.1 + mult * y)).collect()
+pub fn hex_neighbours(pos: &HexCoord) -> Vec<HexCoord> {
+    pos.neighbours().to_vec()
 }
 
-fn setup_hexes(
-    mut writer: EventWriter<SpawnHexEvent>
-){
+/// All coordinates exactly `radius` steps from `centre`, going round the ring in a fixed order
+pub fn hex_ring(centre: HexCoord, radius: i32) -> impl Iterator<Item = HexCoord> {
+    //start at one corner of the ring and walk along each of its six sides
+    let start = centre + HexCoord::DIRECTIONS[4] * radius;
+    let side_len = radius.max(0) as usize;
+    let steps = (0..6).flat_map(move |side| std::iter::repeat_n(HexCoord::DIRECTIONS[side], side_len));
+    let ring = steps.scan(start, |current, step| {
+        let this = *current;
+        *current += step;
+        Some(this)
+    });
+    //a ring of radius 0 is just the centre, which the walk above would miss
+    (radius == 0).then_some(centre).into_iter().chain(ring)
+}
 
-    //For a hexagonal grid with radius of hex_size
-    let hex_size: i32 = 25;
-    for i in -hex_size..=hex_size {
-        let min_j = (i.abs() / 2) - hex_size;
-        let max_j = hex_size - ((i.abs() + 1) / 2);
-        for j in min_j..=max_j{
-            writer.send(SpawnHexEvent{
-                position: (i ,j)
-            });
-        }
+/// All coordinates within `radius` steps of `centre`, starting at the centre and going outwards ring by ring
+pub fn hex_spiral(centre: HexCoord, radius: i32) -> impl Iterator<Item = HexCoord> {
+    (0..=radius).flat_map(move |ring| hex_ring(centre, ring))
+}
+
+/// The tiles a straight line from `a` to `b` passes through, including both ends.
+/// Found by interpolating in cube space and rounding each step back to a tile
+pub fn hex_line(a: HexCoord, b: HexCoord) -> Vec<HexCoord> {
+    let steps = a.distance(b);
+    if steps == 0 {return vec![a];}
+
+    //the line is drawn from the origin and moved into place afterwards, so it comes out the same wherever it is on the map.
+    //The start is nudged slightly so that points exactly on an edge between tiles always round the same way,
+    //which only works while the numbers are small enough for the nudge not to be lost to rounding
+    let offset = b - a;
+    let (nudge_q, nudge_r) = (1e-6, 2e-6);
+    let (dq, dr) = (offset.q as f32 - nudge_q, offset.r as f32 - nudge_r);
+    (0..=steps).map(|step| {
+        let t = step as f32 / steps as f32;
+        a + HexCoord::round(nudge_q + dq * t, nudge_r + dr * t)
+    }).collect()
+}
+
+//keeps each tile entity at the top of its column, the column itself is drawn by its chunk
+fn sync_tile_transforms(
+    mut tiles_q: Query<(&TileHeight, &mut Transform), Changed<TileHeight>>
+) {
+    for (height, mut transform) in tiles_q.iter_mut(){
+        transform.translation.y = height.0;
     }
+}
 
+//move anything standing on a tile onto it, either when it moves to another tile or when the terrain under it changes.
+//Entities on tiles that don't exist yet stay where they are until the tile is spawned
+fn snap_to_tiles(
+    mut on_tile_q: Query<(Ref<OnTile>, &mut Transform)>,
+    changed_heights: Query<(), Changed<TileHeight>>,
+    heights: Query<&TileHeight>,
+    tile_map: Res<HexPositionMap>,
+    layout: Res<HexLayout>
+) {
+    let terrain_changed = !changed_heights.is_empty();
+    for (on_tile, mut transform) in on_tile_q.iter_mut(){
+        if !terrain_changed && !on_tile.is_changed() {continue;}
+        let Some(height) = tile_map.map.get(&on_tile.coord).and_then(|&ent| heights.get(ent).ok()) else {continue;};
 
-    /* //For a simple rectangular grid
-    for i in -10..11{
-        for j in -10..11{
-            writer.send(SpawnHexEvent{
-                position: (i ,j)
-            });
-        }
+        let xz_pos = layout.coord_to_xz(on_tile.coord);
+        transform.translation = Vec3::new(xz_pos.x, height.0 + on_tile.height_offset, xz_pos.y);
+    }
+}
+
+fn setup_hexes(
+    mut writer: EventWriter<SpawnHexEvent>,
+    shape: Res<MapShape>,
+    layout: Res<HexLayout>
+){
+    for position in shape.coords(&layout){
+        writer.send(SpawnHexEvent{position});
     }
-    */
 }
 
 fn spawn_hexes(
     mut commands: Commands,
     mut reader: EventReader<SpawnHexEvent>,
     mut tile_map: ResMut<HexPositionMap>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    handles: Res<HexagonMeshHandles>,
+    layout: Res<HexLayout>,
     mut hex_to_update: ResMut<HexagonsToUpdate>
 ) {
     //stores the neighbours that will be added to the entity in a later system
@@ -133,8 +186,7 @@ fn spawn_hexes(
             }
         }
 
-        let x_pos = x_from_coord(event.position.0, event.position.1);
-        let z_pos = z_from_coord(event.position.0, event.position.1);
+        let xz_pos = layout.coord_to_xz(event.position);
 
 
 
@@ -143,27 +195,17 @@ fn spawn_hexes(
             HexTile{
                 position: event.position,
                 neighbours: neighbours.clone(),
-                explored_state: TileExploredState::Hidden
+                explored_state: TileExploredState::Hidden,
+                movement_cost: 1.0,
+                river_to: None,
+                level: 0
             },
-            SpatialBundle::from(Transform::from_translation(Vec3::new(x_pos, 0.0, z_pos)))
-
-        )).with_children(|par| {
-            par.spawn((
-                MaterialMeshBundle{
-                    mesh: handles.hex_mesh.clone(),
-                    material: materials.add(StandardMaterial::from(Color::linear_rgba(0.0, 0.0, 0.0, 1.0))),
-                    ..Default::default()
-                },
-                RaycastMesh::<()>::default()
-            ));
-            par.spawn(
-                MaterialMeshBundle{
-                    mesh: handles.outline_mesh.clone(),
-                    material: handles.outline_material.clone(),
-                    ..Default::default()
-                }
-            );
-        }).id();
+            TileHeight::default(),
+            TileColour(Color::BLACK),
+            FogTransition::settled(&TileExploredState::Hidden),
+            SpatialBundle::from(Transform::from_translation(Vec3::new(xz_pos.x, 0.0, xz_pos.y)))
+
+        )).id();
 
 
         //update the to_update list of each of those neighbours
@@ -185,16 +227,35 @@ fn spawn_hexes(
 pub struct HexSpawnSet;
 
 
+/// Added to the cost of moving onto a river tile from anywhere other than along the river
+const RIVER_CROSSING_COST: f32 = 2.0;
+/// Added to the cost of moving onto a tile for each level it is above the one being left
+const CLIMB_COST_PER_LEVEL: f32 = 1.0;
+
+
 
 #[derive(Resource, Default)]
 pub struct HexPositionMap{
-    pub map: HashMap<(i32, i32), Entity>
+    pub map: HashMap<HexCoord, Entity>
+}
+
+impl HexPositionMap{
+    /// The tiles exactly `radius` steps from `centre` in `hex_ring` order, skipping any that don't exist
+    #[allow(dead_code)]
+    pub fn ring(&self, centre: HexCoord, radius: i32) -> impl Iterator<Item = (HexCoord, Entity)> + '_ {
+        hex_ring(centre, radius).filter_map(|coord| self.map.get(&coord).map(|&ent| (coord, ent)))
+    }
+
+    /// The tiles within `radius` steps of `centre` in `hex_spiral` order, skipping any that don't exist
+    pub fn spiral(&self, centre: HexCoord, radius: i32) -> impl Iterator<Item = (HexCoord, Entity)> + '_ {
+        hex_spiral(centre, radius).filter_map(|coord| self.map.get(&coord).map(|&ent| (coord, ent)))
+    }
 }
 
 
 #[derive(Event)]
 struct SpawnHexEvent{
-    pub position: (i32, i32)
+    pub position: HexCoord
 }
 
 #[derive(Resource, Default)]
@@ -203,15 +264,73 @@ struct HexagonsToUpdate(pub HashMap<Entity, Vec<Entity>>);
 
 #[derive(Component)]
 pub struct HexTile{
-    pub position: (i32, i32),
+    pub position: HexCoord,
     pub neighbours: Vec<Entity>,
-    pub explored_state: TileExploredState
+    pub explored_state: TileExploredState,
+    /// The cost of moving onto this tile, taken from its `Biome` once terrain generation has run
+    pub movement_cost: f32,
+    /// The neighbouring tile this tile's river flows into, `None` if no river runs through it
+    pub river_to: Option<HexCoord>,
+    /// The tile's height in whole steps when the terrain is terraced, always 0 otherwise
+    pub level: i32
+}
+
+impl HexTile{
+    /// Whether a river runs directly between this tile and `other`, in either direction
+    pub fn shares_river(&self, other: &Self) -> bool {
+        self.river_to == Some(other.position) || other.river_to == Some(self.position)
+    }
+
+    /// Sets the field of `tile` picked out by `field` to `value`, leaving the tile untouched if it's already the same.
+    /// The fog of war is rechecked for every tile that is `Changed<HexTile>`, so terrain generation
+    /// writes through this to avoid marking every tile as changed each time it runs
+    pub fn set_if_neq<T: PartialEq>(tile: &mut Mut<HexTile>, field: impl Fn(&mut HexTile) -> &mut T, value: T) {
+        if *field(tile.bypass_change_detection()) != value {
+            *field(tile) = value;
+        }
+    }
 }
 
 impl GraphVertex for HexTile{
     fn get_neighbours(&self) -> Vec<Entity> {
         self.neighbours.clone()
     }
+
+    //following a river is as easy as open ground, but stepping onto one from the side means fording it.
+    //Climbing up to a higher level costs extra on top, going down is free
+    fn edge_cost(&self, to: &Self) -> f32 {
+        let terrain_cost = match to.river_to{
+            Some(_) if self.shares_river(to) => 1.0,
+            Some(_) => to.movement_cost + RIVER_CROSSING_COST,
+            None => to.movement_cost,
+        };
+        terrain_cost + CLIMB_COST_PER_LEVEL * (to.level - self.level).max(0) as f32
+    }
+}
+
+impl HeuristicVertex for HexTile{
+    //every step costs at least 1, so the step count never overestimates
+    fn heuristic(&self, goal: &Self) -> f32 {
+        self.position.distance(goal.position) as f32
+    }
+}
+
+/// The height of the top of a tile, filled in when the terrain is generated.
+/// Anything that needs to know how high a tile is reads this rather than sampling the noise again,
+/// so that later changes to the terrain such as erosion are seen everywhere
+#[derive(Component, Clone, Copy, PartialEq, Debug, Default)]
+pub struct TileHeight(pub f32);
+
+/// The colour a tile is drawn with before the fog of war is applied. Changing it rebuilds the mesh of the tile's chunk
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct TileColour(pub Color);
+
+/// Keeps an entity standing on top of a tile, following the tile's `TileHeight` whenever it changes
+#[derive(Component, Clone, Copy, Debug)]
+pub struct OnTile{
+    pub coord: HexCoord,
+    /// How far above the top of the tile the entity's origin is
+    pub height_offset: f32
 }
 
 pub enum TileExploredState{
@@ -220,5 +339,76 @@ pub enum TileExploredState{
     Visible
 }
 
+impl TileExploredState{
+    /// How clear a tile in this state is, as passed to the terrain shader which draws the fog of war.
+    /// Fading between two states goes through the ones in between
+    pub fn fog_level(&self) -> f32 {
+        match self{
+            TileExploredState::Hidden => 0.0,
+            TileExploredState::Explored => 1.0,
+            TileExploredState::Visible => 2.0,
+        }
+    }
+}
+
+
+
 
 
+#[cfg(test)]
+mod tests{
+    use bevy::utils::hashbrown::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn ring_has_six_tiles_per_step() {
+        let centre = HexCoord::new(2, -3);
+        assert_eq!(hex_ring(centre, 0).collect::<Vec<_>>(), vec![centre]);
+        for radius in 1..=5{
+            let ring: Vec<HexCoord> = hex_ring(centre, radius).collect();
+            assert_eq!(ring.len(), 6 * radius as usize);
+            assert_eq!(ring.iter().collect::<HashSet<_>>().len(), ring.len());
+            assert!(ring.iter().all(|coord| coord.distance(centre) == radius));
+        }
+    }
+
+    #[test]
+    fn ring_walks_between_neighbours() {
+        let ring: Vec<HexCoord> = hex_ring(HexCoord::ZERO, 3).collect();
+        //each tile is next to the one before it, including going from the last back round to the first
+        for (index, coord) in ring.iter().enumerate(){
+            assert_eq!(coord.distance(ring[(index + 1) % ring.len()]), 1);
+        }
+    }
+
+    #[test]
+    fn line_is_the_same_anywhere_on_the_map() {
+        for start in [HexCoord::new(16, 0), HexCoord::new(-25, 12), HexCoord::new(7, -25)]{
+            for end in hex_spiral(start, 7){
+                let moved: Vec<HexCoord> = hex_line(HexCoord::ZERO, end - start).into_iter().map(|coord| coord + start).collect();
+                assert_eq!(hex_line(start, end), moved, "{:?} to {:?}", start, end);
+            }
+        }
+    }
+
+    #[test]
+    fn line_steps_between_neighbours() {
+        let (start, end) = (HexCoord::new(16, 0), HexCoord::new(12, -4));
+        let line = hex_line(start, end);
+        assert_eq!(line.len(), start.distance(end) as usize + 1);
+        assert_eq!((line[0], line[line.len() - 1]), (start, end));
+        assert!(line.windows(2).all(|pair| pair[0].distance(pair[1]) == 1));
+    }
+
+    #[test]
+    fn spiral_covers_every_tile_nearest_first() {
+        let centre = HexCoord::new(-4, 1);
+        let radius = 4;
+        let spiral: Vec<HexCoord> = hex_spiral(centre, radius).collect();
+        assert_eq!(spiral.len(), (1 + 3 * radius * (radius + 1)) as usize);
+        assert_eq!(spiral[0], centre);
+        assert_eq!(spiral.iter().collect::<HashSet<_>>().len(), spiral.len());
+        assert!(spiral.windows(2).all(|pair| pair[0].distance(centre) <= pair[1].distance(centre)));
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::{color::{Color, Mix}, ecs::{change_detection::DetectChangesMut, component::Component, entity::Entity, query::Changed, system::{Commands, Query, Res}}, utils::hashbrown::{HashMap, HashSet}};
+
+use super::{lakes::Drainage, BiomeProperties, HexCoord, HexTile, TileColour};
+
+
+/// How many tiles have to drain through a tile before it becomes part of a river
+const RIVER_THRESHOLD: f32 = 12.0;
+//tiles whose biome has this tag are where rivers end
+const WATER_TAG: &str = "water";
+
+const RIVER_COLOUR: Color = Color::linear_rgb(0.1, 0.3, 0.9);
+//how far river tiles are tinted towards RIVER_COLOUR, the smallest rivers get the least and it grows with their flow
+const MIN_RIVER_TINT: f32 = 0.4;
+const MAX_RIVER_TINT: f32 = 0.85;
+
+
+/// Marks a tile that a river runs through, the tile it flows into is `HexTile::river_to`
+#[derive(Component, Clone, Copy, Debug)]
+pub struct River{
+    /// The number of tiles, including this one, whose rain drains through this tile
+    pub flow: f32
+}
+
+
+/// The colour a tile is drawn with before the fog of war is applied
+pub fn surface_colour(properties: &BiomeProperties, river: Option<&River>) -> Color {
+    match river{
+        Some(river) => {
+            let tint = MIN_RIVER_TINT * (river.flow / RIVER_THRESHOLD).sqrt();
+            properties.colour.mix(&RIVER_COLOUR, tint.min(MAX_RIVER_TINT))
+        },
+        None => properties.colour,
+    }
+}
+
+
+//runs after the biomes are decided, and again whenever any of them change.
+//Each tile passes everything that drained into it on to its `Drainage::downstream` tile, including lakes,
+//so a river that runs into a lake carries on from wherever the lake spills out.
+//Land tiles with enough flow become rivers, as long as following them downhill reaches water,
+//rivers that would run off the edge of the map are left out
+pub(super) fn generate_rivers(
+    mut commands: Commands,
+    drainage: Res<Drainage>,
+    changed: Query<(), Changed<BiomeProperties>>,
+    mut tiles_q: Query<(Entity, &mut HexTile, &BiomeProperties, &mut TileColour)>,
+) {
+    if changed.is_empty() {return;}
+
+    let water: HashSet<Entity> = tiles_q.iter()
+        .filter(|(_, _, properties, _)| properties.has_tag(WATER_TAG))
+        .map(|(ent, ..)| ent)
+        .collect();
+
+    let mut flow: HashMap<Entity, f32> = drainage.order.iter()
+        .map(|&ent| (ent, if water.contains(&ent) {0.0} else {1.0}))
+        .collect();
+    //highest first, so everything upstream of a tile has been added to it before it passes its flow on
+    for ent in drainage.order.iter().rev(){
+        let Some(down) = drainage.downstream.get(ent) else {continue;};
+        let passed_on = flow[ent];
+        if let Some(down_flow) = flow.get_mut(down){
+            *down_flow += passed_on;
+        }
+    }
+
+    //lowest first, so it's already known whether the tile downstream reaches water
+    let mut reaches_water: HashSet<Entity> = HashSet::new();
+    for ent in drainage.order.iter(){
+        if drainage.downstream.get(ent).is_some_and(|down| water.contains(down) || reaches_water.contains(down)){
+            reaches_water.insert(*ent);
+        }
+    }
+
+    let positions: HashMap<Entity, HexCoord> = tiles_q.iter().map(|(ent, tile, ..)| (ent, tile.position)).collect();
+
+    for (ent, mut tile, properties, mut colour) in tiles_q.iter_mut(){
+        let tile_flow = flow.get(&ent).copied().unwrap_or_default();
+        let river = (tile_flow >= RIVER_THRESHOLD && !water.contains(&ent) && reaches_water.contains(&ent))
+            .then_some(River{flow: tile_flow});
+        let river_to = river.and(drainage.downstream.get(&ent).map(|down| positions[down]));
+
+        HexTile::set_if_neq(&mut tile, |tile| &mut tile.river_to, river_to);
+        match river{
+            Some(river) => commands.entity(ent).insert(river),
+            None => commands.entity(ent).remove::<River>(),
+        };
+
+        //every change to the colour rebuilds the tile's chunk
+        colour.set_if_neq(TileColour(surface_colour(properties, river.as_ref())));
+    }
+}
@@ -4,7 +4,6 @@ use std::ops::{Add, AddAssign, Sub, SubAssign};
 use bevy::math::vec3;
 use bevy::render::camera::{OrthographicProjection, ScalingMode};
 use bevy::{math::Vec3, transform::components::Transform, ecs::{system::{Query, Res, Commands}, query::With, component::Component}, render::camera::Camera, input::{ButtonInput, keyboard::KeyCode}, pbr::{PointLightBundle, PointLight}, time::{Virtual, Time}, core_pipeline::core_3d::Camera3dBundle, app::{Plugin, Startup, Update}};
-use bevy_mod_raycast::deferred::RaycastSource;
 
 pub struct LocalCameraPlugin;
 impl Plugin for LocalCameraPlugin{
@@ -68,10 +67,7 @@ impl CameraFocus{
 fn spawn_camera(
     mut commands: Commands
 ){
-    commands.spawn((
-        CameraFocus::focus_camera_at(Vec3::new(0.0, 0.0, 0.0)),
-        RaycastSource::<()>::new_cursor()
-    ));
+    commands.spawn(CameraFocus::focus_camera_at(Vec3::new(0.0, 0.0, 0.0)));
 
     //light
     commands.spawn(PointLightBundle {
@@ -1,8 +1,10 @@
-use bevy::{app::{Plugin, PreStartup, Startup, Update}, asset::{Assets, Handle}, color::Color, math::vec3, pbr::{MaterialMeshBundle, StandardMaterial}, prelude::{Capsule3d, Commands, Component, EventReader, Query, Res, ResMut, Resource, With}, render::mesh::Mesh, transform::components::Transform};
+use bevy::{app::{Plugin, PreStartup, Startup, Update}, asset::{Assets, Handle}, color::Color, pbr::{MaterialMeshBundle, StandardMaterial}, prelude::{Capsule3d, Commands, Component, EventReader, Query, Res, ResMut, Resource, With}, render::mesh::Mesh};
 
-use crate::random_gens::HeightmapNoise;
+use super::{hex_tile::OnTile, HexCoord, PlayerMovedEvent};
 
-use super::{x_from_coord, z_from_coord, PlayerMovedEvent};
+
+//how far above the top of their tile the character's origin is, so that they stand on it
+const CHARACTER_HEIGHT_OFFSET: f32 = 0.3;
 
 
 
@@ -36,22 +38,18 @@ fn create_handles(
     });
 }
 
+//the player is placed on top of their tile by `OnTile` once the tiles have been spawned
 fn spawn_player(
     char_mesh: Res<CharacterHandles>,
     mut commands: Commands,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    height_noise: Res<HeightmapNoise>
+    mut materials: ResMut<Assets<StandardMaterial>>
 ) {
-    let x_pos = 0.0;
-    let z_pos = 0.0;
-    let y_pos = height_noise.height_at_xz(0.0, 0.0) as f32 + 0.3;
-
     commands.spawn((MaterialMeshBundle{
         mesh: char_mesh.char_mesh.clone(),
         material: materials.add(StandardMaterial::from(Color::linear_rgba(1.0, 1.0, 0.0, 1.0))),
-        transform: Transform::from_translation(vec3(x_pos, y_pos, z_pos)),
         ..Default::default()
     },
+    OnTile{coord: HexCoord::ZERO, height_offset: CHARACTER_HEIGHT_OFFSET},
     CharacterMarker
     ));
 }
@@ -59,15 +57,11 @@ fn spawn_player(
 
 fn move_character(
     mut reader: EventReader<PlayerMovedEvent>,
-    mut char_q: Query<&mut Transform, With<CharacterMarker>>,
-    height_noise: Res<HeightmapNoise>
+    mut char_q: Query<&mut OnTile, With<CharacterMarker>>
 ){
-    let mut char_pos = char_q.single_mut();
+    let mut on_tile = char_q.single_mut();
     for event in reader.read(){
-        let x_pos = x_from_coord(event.to.0, event.to.1);
-        let z_pos = z_from_coord(event.to.0, event.to.1);
-        let y_pos =  height_noise.height_at_xz(x_pos, z_pos) + 0.3;
-        char_pos.translation = vec3(x_pos, y_pos, z_pos);
+        on_tile.coord = event.to;
     }
 
 }
\ No newline at end of file
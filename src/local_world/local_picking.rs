@@ -0,0 +1,150 @@
+use bevy::{app::{Plugin, PreUpdate}, ecs::{query::With, system::{Query, Res, ResMut, Resource}}, math::{Ray3d, Vec2}, render::camera::Camera, transform::components::GlobalTransform, window::{PrimaryWindow, Window}};
+
+use super::hex_tile::{HexCoord, HexLayout, HexPositionMap, TileHeight};
+
+
+pub struct LocalPickingPlugin;
+impl Plugin for LocalPickingPlugin{
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app
+        .init_resource::<CursorHex>()
+        .add_systems(PreUpdate, update_cursor_hex);
+    }
+}
+
+
+//how far along the ray we look for terrain before giving up
+const MAX_PICK_DISTANCE: f32 = 100.0;
+
+
+/// The tile currently under the mouse cursor, if any
+#[derive(Resource, Default)]
+pub struct CursorHex(pub Option<HexCoord>);
+
+
+fn update_cursor_hex(
+    mut cursor_hex: ResMut<CursorHex>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    layout: Res<HexLayout>,
+    tile_map: Res<HexPositionMap>,
+    tiles: Query<&TileHeight>
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single()) else {return;};
+
+    cursor_hex.0 = window.cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .and_then(|ray| pick_hex(ray, &layout, &tile_map, &tiles));
+}
+
+
+/// Finds the first tile hit by `ray`, treating each tile as a column reaching up to its height.
+/// The ray is followed across the grid one tile at a time, so only the tiles under it are looked up
+/// and a tile is found however little of it the ray passes over
+pub fn pick_hex(
+    ray: Ray3d,
+    layout: &HexLayout,
+    tile_map: &HexPositionMap,
+    tiles: &Query<&TileHeight>
+) -> Option<HexCoord> {
+    let origin = Vec2::new(ray.origin.x, ray.origin.z);
+    let direction = Vec2::new(ray.direction.x, ray.direction.z);
+    let height_at = |t: f32| ray.origin.y + ray.direction.y * t;
+
+    //the outward direction of each side of a tile, and how far it is from the centre
+    let sides: Vec<(Vec2, f32)> = (0..6).map(|index| {
+        let middle = 0.5 * (layout.corner(index) + layout.corner((index + 1) % 6));
+        let middle = Vec2::new(middle.x, middle.z);
+        (middle.normalize(), middle.length())
+    }).collect();
+
+    let mut coord = layout.xz_to_coord(origin.x, origin.y);
+    let mut enter = 0.0;
+    while enter < MAX_PICK_DISTANCE{
+        let centre = layout.coord_to_xz(coord);
+        //the ray leaves through whichever side it reaches first, into the tile on the other side of it.
+        //A ray pointing straight down never leaves
+        let (exit, next_centre) = sides.iter()
+            .filter(|(normal, _)| direction.dot(*normal) > 0.0)
+            .map(|&(normal, apothem)| {
+                let exit = (apothem - (origin - centre).dot(normal)) / direction.dot(normal);
+                (exit, centre + 2.0 * apothem * normal)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap_or((MAX_PICK_DISTANCE, centre));
+        let exit = exit.min(MAX_PICK_DISTANCE);
+
+        //the ray is at its lowest over the tile at one end or the other of its way across
+        let hit = tile_map.map.get(&coord)
+            .and_then(|&ent| tiles.get(ent).ok())
+            .is_some_and(|height| height_at(enter).min(height_at(exit)) <= height.0);
+        if hit {return Some(coord);}
+
+        coord = layout.xz_to_coord(next_centre.x, next_centre.y);
+        enter = exit;
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests{
+    use bevy::{ecs::{system::SystemState, world::World}, math::Vec3, utils::hashbrown::HashMap};
+
+    use super::*;
+    use crate::local_world::hex_tile::hex_spiral;
+
+    //flat ground at height 0 within 4 tiles of the origin, apart from the tiles given
+    fn terrain(raised: &[(HexCoord, f32)]) -> (World, HexPositionMap) {
+        let mut world = World::new();
+        let map = hex_spiral(HexCoord::ZERO, 4).map(|coord| {
+            let height = raised.iter().find(|(raised, _)| *raised == coord).map_or(0.0, |(_, height)| *height);
+            (coord, world.spawn(TileHeight(height)).id())
+        }).collect::<HashMap<_, _>>();
+        (world, HexPositionMap{map})
+    }
+
+    fn pick(world: &mut World, tile_map: &HexPositionMap, layout: &HexLayout, origin: Vec3, direction: Vec3) -> Option<HexCoord> {
+        let mut state = SystemState::<Query<&TileHeight>>::new(world);
+        let tiles = state.get(world);
+        pick_hex(Ray3d::new(origin, direction), layout, tile_map, &tiles)
+    }
+
+    #[test]
+    fn straight_down_picks_the_tile_below() {
+        let layout = HexLayout::default();
+        let (mut world, tile_map) = terrain(&[]);
+        for coord in [HexCoord::ZERO, HexCoord::new(2, -1), HexCoord::new(-3, 3)]{
+            let centre = layout.coord_to_xz(coord);
+            let picked = pick(&mut world, &tile_map, &layout, Vec3::new(centre.x, 10.0, centre.y), Vec3::NEG_Y);
+            assert_eq!(picked, Some(coord));
+        }
+    }
+
+    #[test]
+    fn slanted_ray_hits_a_raised_tile_in_front() {
+        let layout = HexLayout::default();
+        let raised = HexCoord::new(1, 0);
+        let (mut world, tile_map) = terrain(&[(raised, 2.0)]);
+        let target = layout.coord_to_xz(HexCoord::new(2, 0));
+        let start = layout.coord_to_xz(HexCoord::new(-2, 0));
+        //aimed at the ground behind the raised tile, which is in the way
+        let origin = Vec3::new(start.x, 3.0, start.y);
+        let picked = pick(&mut world, &tile_map, &layout, origin, Vec3::new(target.x, 0.0, target.y) - origin);
+        assert_eq!(picked, Some(raised));
+    }
+
+    #[test]
+    fn grazing_a_corner_still_hits() {
+        let layout = HexLayout::default();
+        let raised = HexCoord::new(1, 0);
+        let (mut world, tile_map) = terrain(&[(raised, 1.0)]);
+        //a level ray above the flat ground that cuts across one corner of the raised tile for a tiny distance
+        let corner = layout.coord_to_xz(raised) + Vec2::new(layout.corner(0).x, layout.corner(0).z);
+        let side = Vec2::new(layout.corner(0).z, -layout.corner(0).x).normalize();
+        let inside = corner - 0.01 * Vec2::new(layout.corner(0).x, layout.corner(0).z).normalize();
+        let origin = Vec3::new(inside.x - 20.0 * side.x, 0.5, inside.y - 20.0 * side.y);
+        let picked = pick(&mut world, &tile_map, &layout, origin, Vec3::new(side.x, 0.0, side.y));
+        assert_eq!(picked, Some(raised));
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::ecs::{entity::Entity, system::Query};
+
+use super::hex_tile::{hex_line, HexCoord, HexPositionMap, HexTile, TileHeight};
+
+
+/// How far above the top of their tile the player sees from
+pub const EYE_HEIGHT: f32 = 0.3;
+
+/// How far the player can see from flat ground
+pub const BASE_VIEW_RADIUS: i32 = 3;
+/// The most the view radius can be extended by standing up high
+pub const MAX_EXTRA_VIEW_RADIUS: i32 = 4;
+//how far above the surrounding tiles the player has to be to see one tile further
+const HEIGHT_PER_EXTRA_RADIUS: f32 = 0.2;
+
+
+fn tile_height(coord: &HexCoord, tile_map: &HexPositionMap, tiles: &Query<(&HexTile, &TileHeight)>) -> Option<f32> {
+    tile_map.map.get(coord)
+        .and_then(|&ent| tiles.get(ent).ok())
+        .map(|(_, height)| height.0)
+}
+
+
+/// How far can be seen from `centre`, extending `base_radius` by how far `centre` stands above
+/// the average height of the tiles within `base_radius` of it
+pub fn view_radius(
+    centre: HexCoord,
+    base_radius: i32,
+    tile_map: &HexPositionMap,
+    tiles: &Query<(&HexTile, &TileHeight)>
+) -> i32 {
+    let Some(centre_height) = tile_height(&centre, tile_map, tiles) else {return base_radius;};
+
+    let surrounding: Vec<f32> = tile_map.spiral(centre, base_radius).skip(1)
+        .filter_map(|(coord, _)| tile_height(&coord, tile_map, tiles))
+        .collect();
+    if surrounding.is_empty() {return base_radius;}
+
+    let prominence = centre_height - surrounding.iter().sum::<f32>() / surrounding.len() as f32;
+    let extra = (prominence / HEIGHT_PER_EXTRA_RADIUS).floor() as i32;
+    base_radius + extra.clamp(0, MAX_EXTRA_VIEW_RADIUS)
+}
+
+
+/// All the tiles the player can see when standing at `centre`, nearest first.
+/// Each tile within the (height adjusted) `view_radius` is checked with its own sightline,
+/// the radius is small enough that this is cheap and it avoids the gaps shadowcasting leaves on a hex grid
+pub fn field_of_view(
+    centre: HexCoord,
+    base_radius: i32,
+    tile_map: &HexPositionMap,
+    tiles: &Query<(&HexTile, &TileHeight)>,
+    terrace_step: Option<f32>
+) -> Vec<(HexCoord, Entity)> {
+    let radius = view_radius(centre, base_radius, tile_map, tiles);
+    tile_map.spiral(centre, radius)
+        .filter(|(coord, _)| line_of_sight(centre, *coord, EYE_HEIGHT, tile_map, tiles, terrace_step))
+        .collect()
+}
+
+
+/// Whether the top of the tile at `to` can be seen from `eye_height` above the tile at `from`.
+/// The sightline is blocked by any tile along `hex_line` that rises above it,
+/// tiles that don't exist are treated as not blocking anything.
+/// With terracing on (`terrace_step` is the size of each level) the tiles' `HexTile::level`s are compared instead,
+/// so only a tile on a higher level than the sightline is passing at blocks it
+pub fn line_of_sight(
+    from: HexCoord,
+    to: HexCoord,
+    eye_height: f32,
+    tile_map: &HexPositionMap,
+    tiles: &Query<(&HexTile, &TileHeight)>,
+    terrace_step: Option<f32>
+) -> bool {
+    let height_at = |coord: &HexCoord| tile_height(coord, tile_map, tiles);
+
+    let (Some(start_height), Some(end_height)) = (height_at(&from), height_at(&to)) else {return false;};
+    let start_height = start_height + eye_height;
+
+    let line = hex_line(from, to);
+    let steps = (line.len() - 1) as f32;
+
+    //only the tiles strictly between the two ends can get in the way
+    line.iter().enumerate().skip(1).take(line.len().saturating_sub(2)).all(|(step, coord)| {
+        let sight_height = start_height + (end_height - start_height) * step as f32 / steps;
+        let Some((tile, height)) = tile_map.map.get(coord).and_then(|&ent| tiles.get(ent).ok()) else {return true;};
+        match terrace_step{
+            Some(step) => tile.level <= (sight_height / step).round() as i32,
+            None => height.0 <= sight_height,
+        }
+    })
+}
@@ -1,29 +1,39 @@
 mod hex_tile;
 mod local_camera;
 mod local_character;
+mod local_picking;
+mod local_visibility;
 
-use bevy::{app::{Plugin, Update}, asset::{Assets, Handle}, color::Color, ecs::{event::{Event, EventReader}, system::{Commands, Query, Res}}, hierarchy::Children, pbr::StandardMaterial, prelude::{Changed, IntoSystemConfigs}};
+use bevy::{app::{Plugin, Update}, ecs::{event::{Event, EventReader}, system::{Query, Res}}, prelude::{Changed, IntoSystemConfigs}, time::{Time, Virtual}};
 
 use local_camera::LocalCameraPlugin;
 use local_character::LocalCharacterPlugin;
+use local_picking::LocalPickingPlugin;
+use local_visibility::{field_of_view, BASE_VIEW_RADIUS, MAX_EXTRA_VIEW_RADIUS};
 use hex_tile::HexPlugin;
 
-use crate::graph_functions;
+use self::hex_tile::{FogTransition, HexPositionMap, TileExploredState, TileHeight};
+use crate::random_gens::TerrainParams;
 
-use self::hex_tile::{ColourTransition, HexPositionMap, TileExploredState};
 
+pub use hex_tile::{HexCoord, HexLayout, HexOrientation, HexTile, MapShape}; ///////////////////////////
+pub use local_picking::CursorHex;
 
-pub use hex_tile::{x_from_coord, z_from_coord, HexTile}; ///////////////////////////
+#[derive(Default)]
+pub struct LocalWorldPlugin{
+    pub map_shape: MapShape,
+    pub layout: HexLayout
+}
 
-pub struct LocalWorldPlugin;
 impl Plugin for LocalWorldPlugin{
     fn build(&self, app: &mut bevy::prelude::App) {
         app
         .add_event::<PlayerMovedEvent>()
-        .add_plugins(HexPlugin)
+        .add_plugins(HexPlugin{shape: self.map_shape.clone(), layout: self.layout})
         .add_plugins(LocalCameraPlugin)
         .add_plugins(LocalCharacterPlugin)
-        .add_systems(Update, (update_tile_states, change_tile_colours.after(update_tile_states)));
+        .add_plugins(LocalPickingPlugin)
+        .add_systems(Update, (update_tile_states, start_fog_transitions.after(update_tile_states)));
     }
 }
 
@@ -34,54 +44,44 @@ impl Plugin for LocalWorldPlugin{
 pub fn update_tile_states(
     tiles_map: Res<HexPositionMap>,
     mut reader: EventReader<PlayerMovedEvent>,
-    mut tiles: Query<&mut HexTile>
+    mut tiles: Query<(&mut HexTile, &TileHeight)>,
+    params: Res<TerrainParams>
 ) {
     for event in reader.read(){
-        let start_ent = *tiles_map.map.get(&event.from).unwrap();
-        let end_ent = *tiles_map.map.get(&event.to).unwrap();
-        let left = graph_functions::within_steps(start_ent, 3, &tiles.to_readonly()).unwrap();
-        let entered = graph_functions::within_steps(end_ent, 3, &tiles.to_readonly()).unwrap();
-
-        for (ent, _) in left.into_iter(){
-            tiles.get_mut(ent).unwrap().explored_state = TileExploredState::Explored;
+        //anything that was in view from the old position could now be out of sight
+        for (_, ent) in tiles_map.spiral(event.from, BASE_VIEW_RADIUS + MAX_EXTRA_VIEW_RADIUS){
+            let (mut tile, _) = tiles.get_mut(ent).unwrap();
+            if matches!(tile.explored_state, TileExploredState::Visible){
+                tile.explored_state = TileExploredState::Explored;
+            }
         }
-        for (ent, _) in entered.into_iter(){
-            tiles.get_mut(ent).unwrap().explored_state = TileExploredState::Visible;
+        //field_of_view is in spiral order so the reveal always runs from the player outwards
+        for (_, ent) in field_of_view(event.to, BASE_VIEW_RADIUS, &tiles_map, &tiles.to_readonly(), params.terrace_step){
+            tiles.get_mut(ent).unwrap().0.explored_state = TileExploredState::Visible;
         }
     }
 }
 
 
 
-fn change_tile_colours(
-    mut commands: Commands,
-    col_parent: Query<(&HexTile, &Children), Changed<HexTile>>,
-    colours: Query<&Handle<StandardMaterial>>,
-    mats: Res<Assets<StandardMaterial>>,
+//the fade itself happens in the terrain shader, so this only has to note when it starts
+fn start_fog_transitions(
+    mut tiles: Query<(&HexTile, &mut FogTransition), Changed<HexTile>>,
+    time: Res<Time<Virtual>>
 ) {
-    //get the current colour of the tile and add transition
-    for (tile, children) in col_parent.iter(){
-        let new_colour = match tile.explored_state{
-            TileExploredState::Hidden => continue,
-            TileExploredState::Explored => Color::linear_rgba(0.5, 0.5, 0.5, 1.0),
-            TileExploredState::Visible => Color::linear_rgba(0.0, 1.0, 0.0, 1.0),
-        };
-        for &child in children{
-            if let Ok(handle) = colours.get(child){
-                let current_col = mats.get(handle).unwrap().base_color;
-                commands.entity(child).insert(ColourTransition::new(current_col, new_colour, 2.0));
-            }
-        }
+    for (tile, mut transition) in tiles.iter_mut(){
+        //starting a transition rebuilds the tile's chunk, so don't start one that wouldn't change anything
+        if transition.is_heading_to(&tile.explored_state) {continue;}
+        transition.fade_to(&tile.explored_state, &time);
     }
-    
 }
 
 
 
 #[derive(Event)]
 pub struct PlayerMovedEvent{
-    pub from: (i32, i32),
-    pub to: (i32, i32)
+    pub from: HexCoord,
+    pub to: HexCoord
 }
 
 
@@ -4,8 +4,7 @@ mod player;
 mod random_gens;
 
 use bevy::prelude::*;
-use bevy_mod_raycast::deferred::{RaycastSource, DeferredRaycastingPlugin};
-use local_world::{HexTile, LocalWorldPlugin, PlayerMovedEvent};
+use local_world::{CursorHex, HexCoord, HexLayout, HexOrientation, LocalWorldPlugin, MapShape, PlayerMovedEvent};
 use random_gens::RandomPlugin;
 
 
@@ -20,9 +19,11 @@ fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .init_state::<GameState>()
-        .add_plugins(DeferredRaycastingPlugin::<()>::default())
         .add_plugins(RandomPlugin)
-        .add_plugins(LocalWorldPlugin)
+        .add_plugins(LocalWorldPlugin{
+            map_shape: MapShape::Hexagon{radius: 25},
+            layout: HexLayout{orientation: HexOrientation::Flat, ..Default::default()}
+        })
         .add_systems(Update, test_move)
         .run();
 }
@@ -38,12 +39,10 @@ pub enum GameState{
 
 fn test_move(
     mut state: ResMut<NextState<GameState>>,
-    mut current_pos: Local<(i32, i32)>,
+    mut current_pos: Local<HexCoord>,
     input: Res<ButtonInput<MouseButton>>,
     mut writer: EventWriter<PlayerMovedEvent>,
-    raycast: Query<&RaycastSource<()>>,
-    meshes: Query<&Parent, With<Handle<Mesh>>>,
-    hexes: Query<&HexTile>
+    cursor_hex: Res<CursorHex>
 ) {
 
     if input.just_pressed(MouseButton::Right){
@@ -51,8 +50,7 @@ fn test_move(
     }
     
     if input.just_pressed(MouseButton::Left){
-        if let Some((ent, _)) = raycast.single().get_nearest_intersection(){
-            let new_pos = hexes.get(meshes.get(ent).unwrap().get()).unwrap().position;
+        if let Some(new_pos) = cursor_hex.0{
             writer.send(PlayerMovedEvent {
                 from: *current_pos, 
                 to: new_pos
@@ -1,32 +1,587 @@
-use std::fs;
+use std::{env, fs, hash::{BuildHasher, RandomState}, time::SystemTime};
 
-use bevy::{app::Plugin, prelude::Resource};
-use noise::{NoiseFn, OpenSimplex, RidgedMulti};
+use bevy::{app::{Plugin, Startup, Update}, math::Vec2, utils::hashbrown::HashMap, asset::{io::Reader, Asset, AssetApp, AssetEvent, AssetLoader, AssetServer, Assets, AsyncReadExt, Handle, LoadContext}, log::{info, warn}, prelude::{resource_changed, resource_changed_or_removed, Commands, Condition, EventReader, IntoSystemConfigs, Res, ResMut, Resource}, reflect::TypePath};
+use noise::{Billow, Fbm, HybridMulti, MultiFractal, NoiseFn, OpenSimplex, RidgedMulti};
+use serde::Deserialize;
+use thiserror::Error;
 
-use crate::local_world::{x_from_coord, z_from_coord};
+use crate::local_world::{HexCoord, HexLayout, MapShape};
 
 
+const SEED_ARG: &str = "--seed";
+const SEED_ENV_VAR: &str = "HEX_SEED";
+const SEED_FILE: &str = "./assets/seed.txt";
+const TERRAIN_PARAMS_PATH: &str = "default.terrain.ron";
 
 
 pub struct RandomPlugin;
 impl Plugin for RandomPlugin{
     fn build(&self, app: &mut bevy::prelude::App) {
-        let seed = fs::read_to_string("./assets/seed.txt").expect("Unable to read file")
-        .parse().expect("Failed to parse seed");
-        app.insert_resource(HeightmapNoise(RidgedMulti::new(seed)));
+        //a seed inserted before the plugin is added takes priority over everything else
+        let seed = match app.world().get_resource::<WorldSeed>(){
+            Some(&seed) => seed,
+            None => WorldSeed::from_sources(),
+        };
+        let params = TerrainParams::default();
+        //the map is normally set up by `LocalWorldPlugin`, which may not have been added yet,
+        //but `rebuild_heightmap_noise` runs again once it has
+        let shape = app.world().get_resource::<MapShape>().cloned().unwrap_or_default();
+        let layout = app.world().get_resource::<HexLayout>().copied().unwrap_or_default();
+        app
+        .init_asset::<TerrainParams>()
+        .init_asset_loader::<TerrainParamsLoader>()
+        .insert_resource(seed)
+        .insert_resource(HeightmapNoise::new(seed, &params, &shape, layout))
+        .insert_resource(params)
+        .insert_resource(MoistureNoise::new(seed))
+        .insert_resource(TemperatureNoise::new(seed))
+        .add_systems(Startup, load_terrain_params)
+        .add_systems(Update, (
+            update_terrain_params,
+            rebuild_heightmap_noise.run_if(resource_changed::<TerrainParams>.or_else(resource_changed_or_removed::<MapShape>()))
+        ).chain());
     }
 }
 
 
+// ============================
+// Systems
+// ============================
+
+fn load_terrain_params(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.insert_resource(TerrainParamsHandle(asset_server.load(TERRAIN_PARAMS_PATH)));
+}
+
+//copy the params file into the resource whenever it is loaded or edited
+fn update_terrain_params(
+    handle: Res<TerrainParamsHandle>,
+    assets: Res<Assets<TerrainParams>>,
+    mut events: EventReader<AssetEvent<TerrainParams>>,
+    mut params: ResMut<TerrainParams>
+) {
+    for event in events.read(){
+        if event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0){
+            if let Some(new_params) = assets.get(&handle.0){
+                info!("Updating terrain parameters");
+                *params = new_params.clone();
+            }
+        }
+    }
+}
+
+fn rebuild_heightmap_noise(
+    mut commands: Commands,
+    seed: Res<WorldSeed>,
+    params: Res<TerrainParams>,
+    shape: Option<Res<MapShape>>,
+    layout: Option<Res<HexLayout>>
+) {
+    let shape = shape.map(|shape| shape.clone()).unwrap_or_default();
+    let layout = layout.map(|layout| *layout).unwrap_or_default();
+    commands.insert_resource(HeightmapNoise::new(*seed, &params, &shape, layout));
+}
+
+
+/// The seed every random generator in the world is derived from.
+/// Logged on startup so that a run can be reproduced by passing it back in with `--seed`
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct WorldSeed(pub u32);
+
+impl WorldSeed{
+
+    /// Looks for a seed in the `--seed` command line argument, then the `HEX_SEED` environment variable,
+    /// then `assets/seed.txt`, and otherwise picks one at random.
+    /// Any source that is present but can't be parsed is reported and skipped
+    pub fn from_sources() -> Self {
+        let sources = [
+            ("command line", seed_from_args()),
+            ("environment", env::var(SEED_ENV_VAR).ok()),
+            ("seed file", fs::read_to_string(SEED_FILE).ok()),
+        ];
+
+        for (source, value) in sources{
+            let Some(value) = value else {continue;};
+            let value = value.trim();
+            //an empty file just means no seed has been chosen
+            if value.is_empty() {continue;}
+
+            match value.parse(){
+                Ok(seed) => {
+                    info!("Using world seed {} from the {}", seed, source);
+                    return Self(seed);
+                },
+                Err(err) => warn!("Ignoring world seed {:?} from the {}: {}", value, source, err),
+            }
+        }
+
+        let seed = RandomState::new().hash_one(SystemTime::now()) as u32;
+        info!("Using random world seed {}", seed);
+        Self(seed)
+    }
+
+    /// A seed for a particular generator, so that generators sharing the world seed don't produce matching patterns
+    pub fn derive(&self, purpose: &str) -> u32 {
+        mix(self.0 as u64 ^ hash_str(purpose)) as u32
+    }
+}
+
+//the value following --seed, accepting both `--seed 123` and `--seed=123`
+fn seed_from_args() -> Option<String> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next(){
+        if arg == SEED_ARG {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(SEED_ARG).and_then(|rest| rest.strip_prefix('=')){
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+
+#[derive(Resource)]
+struct TerrainParamsHandle(Handle<TerrainParams>);
+
+
+/// The kind of noise the terrain height is generated from
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NoiseType{
+    RidgedMulti,
+    Fbm,
+    Billow,
+    HybridMulti
+}
+
+/// Settings for generating the terrain height, loaded from a `.terrain.ron` file that is
+/// watched for changes, with the tiles regenerated in place whenever it is edited
+#[derive(Asset, Resource, TypePath, Deserialize, Clone, Debug)]
+pub struct TerrainParams{
+    pub noise_type: NoiseType,
+    pub octaves: usize,
+    pub frequency: f64,
+    /// Added to the sample position, to move away from the noise's origin where it is always 0
+    pub offset: f64,
+    /// World distance covered by one unit of noise, larger values give broader features
+    pub scale: f64,
+    /// Multiplies the final height
+    pub height_multiplier: f32,
+    /// Heights below this are under the sea
+    #[serde(default = "default_sea_level")]
+    pub sea_level: f32,
+    /// Applied in order to the point the noise is sampled at, bending coastlines and ridges
+    #[serde(default)]
+    pub warps: Vec<DomainWarp>,
+    /// Applied in order to the sampled height, lowering it towards the edge of the map
+    #[serde(default)]
+    pub falloffs: Vec<Falloff>,
+    /// Erosion applied to the tile heights after they are sampled, off if left out
+    #[serde(default)]
+    pub erosion: ErosionParams,
+    /// If set, tile heights are rounded to multiples of this, giving each tile a whole number elevation level
+    #[serde(default)]
+    pub terrace_step: Option<f32>
+}
+
+fn default_sea_level() -> f32 {
+    -0.2
+}
+
+/// Moves the point the height is sampled at by a smooth random amount
+#[derive(Deserialize, Clone, Debug)]
+pub struct DomainWarp{
+    /// The furthest a point can be moved, in world units
+    pub strength: f64,
+    /// World distance covered by one unit of the warping noise, larger values give broader bends
+    pub scale: f64
+}
+
+/// Lowers the height the further out a point is, so the map is surrounded by sea
+#[derive(Deserialize, Clone, Debug)]
+pub struct Falloff{
+    pub shape: FalloffShape,
+    /// How far out the height starts dropping, from 0 at the centre to 1 at the edge
+    pub start: f32,
+    /// How much the height is lowered by at the edge
+    pub depth: f32
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FalloffShape{
+    /// Measured by straight line distance from the origin, giving a round island
+    Radial,
+    /// Measured by steps in from the nearest edge of the `MapShape`, so the coast follows the map's outline
+    Map
+}
+
+/// Settings for wearing down the terrain once the heights have been sampled from the noise.
+/// Either kind of erosion is skipped when its number of iterations is 0
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ErosionParams{
+    /// Times rain is dropped on every tile, each drop runs downhill carrying soil away from slopes and into hollows
+    pub hydraulic_iterations: usize,
+    /// Fraction of the soil a drop could still carry that it picks up at each step
+    pub erosion_rate: f32,
+    /// Fraction of the soil over what a drop can carry that it drops at each step
+    pub deposition_rate: f32,
+    /// How much soil a drop can carry for each unit of slope and water
+    pub sediment_capacity: f32,
+    /// Fraction of a drop's water lost at each step
+    pub evaporation: f32,
+    /// Times steep slopes crumble onto their lower neighbours
+    pub thermal_iterations: usize,
+    /// The steepest height difference between neighbours that doesn't crumble
+    pub talus: f32,
+    /// Fraction of the height over `talus` that crumbles in each iteration
+    pub thermal_rate: f32
+}
+
+impl Default for ErosionParams{
+    fn default() -> Self {
+        Self {
+            hydraulic_iterations: 0,
+            erosion_rate: 0.3,
+            deposition_rate: 0.3,
+            sediment_capacity: 1.0,
+            evaporation: 0.1,
+            thermal_iterations: 0,
+            talus: 0.15,
+            thermal_rate: 0.5
+        }
+    }
+}
+
+impl Default for TerrainParams{
+    fn default() -> Self {
+        Self {
+            noise_type: NoiseType::RidgedMulti,
+            octaves: RidgedMulti::<OpenSimplex>::DEFAULT_OCTAVE_COUNT,
+            frequency: RidgedMulti::<OpenSimplex>::DEFAULT_FREQUENCY,
+            offset: 0.46721,
+            scale: 10.0,
+            height_multiplier: 1.0,
+            sea_level: default_sea_level(),
+            warps: Vec::new(),
+            falloffs: Vec::new(),
+            erosion: ErosionParams::default(),
+            terrace_step: None
+        }
+    }
+}
+
+impl TerrainParams{
+    fn validate(&self) -> Result<(), TerrainParamsError> {
+        if !(1..=RidgedMulti::<OpenSimplex>::MAX_OCTAVES).contains(&self.octaves) {
+            return Err(TerrainParamsError::Invalid(format!("octaves must be between 1 and {}", RidgedMulti::<OpenSimplex>::MAX_OCTAVES)));
+        }
+        if [self.frequency, self.scale].iter().any(|value| value.is_nan() || *value <= 0.0) {
+            return Err(TerrainParamsError::Invalid("frequency and scale must both be above 0".to_string()));
+        }
+        if !self.offset.is_finite() || !self.height_multiplier.is_finite() {
+            return Err(TerrainParamsError::Invalid("offset and height_multiplier must be finite".to_string()));
+        }
+        if !self.sea_level.is_finite() {
+            return Err(TerrainParamsError::Invalid("sea_level must be finite".to_string()));
+        }
+        if self.warps.iter().any(|warp| !warp.strength.is_finite() || warp.scale.is_nan() || warp.scale <= 0.0) {
+            return Err(TerrainParamsError::Invalid("warps need a finite strength and a scale above 0".to_string()));
+        }
+        if self.falloffs.iter().any(|falloff| !(0.0..1.0).contains(&falloff.start) || !falloff.depth.is_finite()) {
+            return Err(TerrainParamsError::Invalid("falloffs need a start from 0 up to 1 and a finite depth".to_string()));
+        }
+        if self.terrace_step.is_some_and(|step| step.is_nan() || step <= 0.0) {
+            return Err(TerrainParamsError::Invalid("terrace_step must be above 0".to_string()));
+        }
+        let erosion = &self.erosion;
+        let fractions = [erosion.erosion_rate, erosion.deposition_rate, erosion.evaporation, erosion.thermal_rate];
+        if fractions.iter().any(|fraction| !(0.0..=1.0).contains(fraction)) {
+            return Err(TerrainParamsError::Invalid("erosion and deposition rates, evaporation and thermal_rate must be between 0 and 1".to_string()));
+        }
+        if [erosion.sediment_capacity, erosion.talus].iter().any(|value| value.is_nan() || *value < 0.0) {
+            return Err(TerrainParamsError::Invalid("sediment_capacity and talus can't be negative".to_string()));
+        }
+        Ok(())
+    }
+}
+
+
+#[derive(Default)]
+struct TerrainParamsLoader;
+
+#[derive(Debug, Error)]
+enum TerrainParamsError{
+    #[error("could not read terrain parameters: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse terrain parameters: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("invalid terrain parameters: {0}")]
+    Invalid(String)
+}
+
+impl AssetLoader for TerrainParamsLoader{
+    type Asset = TerrainParams;
+    type Settings = ();
+    type Error = TerrainParamsError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let params: TerrainParams = ron::de::from_bytes(&bytes)?;
+        params.validate()?;
+        Ok(params)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["terrain.ron"]
+    }
+}
+
+
+/// The terrain height at any point, built from the `TerrainParams` with its warps and falloffs applied
 #[derive(Resource)]
-pub struct HeightmapNoise(pub RidgedMulti<OpenSimplex>);
+pub struct HeightmapNoise{
+    noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+    //a pair of noise fields for each of the params' warps, one moving points along x and one along z
+    warps: Vec<(Fbm<OpenSimplex>, Fbm<OpenSimplex>)>,
+    footprint: MapFootprint,
+    params: TerrainParams
+}
+
+//what the falloffs need to know about the map, worked out once when the noise is built
+struct MapFootprint{
+    layout: HexLayout,
+    //distance from the origin to the furthest tile centre
+    radius: f32,
+    //for each tile, how close it is to the edge of the map from 0 in the middle to 1 on the edge
+    edge_fraction: HashMap<HexCoord, f32>
+}
+
+impl MapFootprint{
+    fn new(shape: &MapShape, layout: HexLayout) -> Self {
+        let radius = shape.coords(&layout).into_iter()
+            .map(|coord| layout.coord_to_xz(coord).length())
+            .fold(0.0, f32::max);
+        let distances = shape.edge_distances(&layout);
+        let deepest = distances.values().copied().max().unwrap_or(1);
+        let edge_fraction = distances.into_iter()
+            .map(|(coord, distance)| (coord, 1.0 - (distance - 1) as f32 / (deepest - 1).max(1) as f32))
+            .collect();
+        Self {layout, radius, edge_fraction}
+    }
+
+    //how far out the point is for the given shape of falloff, from 0 at the centre to 1 at the edge
+    fn fraction(&self, shape: FalloffShape, x: f32, z: f32) -> f32 {
+        match shape{
+            FalloffShape::Radial => Vec2::new(x, z).length() / self.radius.max(f32::EPSILON),
+            //anywhere off the map counts as being on the edge
+            FalloffShape::Map => self.edge_fraction.get(&self.layout.xz_to_coord(x, z)).copied().unwrap_or(1.0),
+        }
+    }
+}
 
 impl HeightmapNoise{
+    pub fn new(seed: WorldSeed, params: &TerrainParams, shape: &MapShape, layout: HexLayout) -> Self {
+        let noise: Box<dyn NoiseFn<f64, 2> + Send + Sync> = match params.noise_type{
+            NoiseType::RidgedMulti => Box::new(RidgedMulti::<OpenSimplex>::new(seed.0).set_octaves(params.octaves).set_frequency(params.frequency)),
+            NoiseType::Fbm => Box::new(Fbm::<OpenSimplex>::new(seed.0).set_octaves(params.octaves).set_frequency(params.frequency)),
+            NoiseType::Billow => Box::new(Billow::<OpenSimplex>::new(seed.0).set_octaves(params.octaves).set_frequency(params.frequency)),
+            NoiseType::HybridMulti => Box::new(HybridMulti::<OpenSimplex>::new(seed.0).set_octaves(params.octaves).set_frequency(params.frequency)),
+        };
+        let warps = (0..params.warps.len()).map(|index| (
+            Fbm::new(seed.derive(&format!("warp {} x", index))),
+            Fbm::new(seed.derive(&format!("warp {} z", index)))
+        )).collect();
+        Self {noise, warps, footprint: MapFootprint::new(shape, layout), params: params.clone()}
+    }
+
     pub fn height_at_xz(&self, x: f32, z: f32) -> f32 {
-        self.0.get([x as f64 / 10.0 + 0.46721, z as f64 / 10.0 + 0.46721]) as f32
+        let (mut x, mut z) = (x as f64, z as f64);
+        for (warp, (x_noise, z_noise)) in self.params.warps.iter().zip(self.warps.iter()){
+            let point = [x / warp.scale, z / warp.scale];
+            (x, z) = (x + warp.strength * x_noise.get(point), z + warp.strength * z_noise.get(point));
+        }
+
+        let (scale, offset) = (self.params.scale, self.params.offset);
+        let mut height = self.noise.get([x / scale + offset, z / scale + offset]) as f32 * self.params.height_multiplier;
+
+        //the falloffs use the warped point too, so the coast gets bent the same way as the terrain
+        for falloff in self.params.falloffs.iter(){
+            let fraction = self.footprint.fraction(falloff.shape, x as f32, z as f32);
+            let t = ((fraction - falloff.start) / (1.0 - falloff.start)).clamp(0.0, 1.0);
+            //smoothstep, so the drop eases in rather than starting at a crease
+            height -= falloff.depth * t * t * (3.0 - 2.0 * t);
+        }
+        height
+    }
+
+    pub fn height_at_coord(&self, coord: HexCoord, layout: &HexLayout) -> f32 {
+        let xz_pos = layout.coord_to_xz(coord);
+        self.height_at_xz(xz_pos.x, xz_pos.y)
+    }
+}
+
+
+//moisture and temperature vary over larger distances than the terrain height
+const CLIMATE_SCALE: f64 = 25.0;
+//how much colder it gets for each unit of height above 0
+const TEMPERATURE_LAPSE_RATE: f32 = 0.5;
+
+
+#[derive(Resource)]
+pub struct MoistureNoise(pub Fbm<OpenSimplex>);
+
+impl MoistureNoise{
+    pub fn new(seed: WorldSeed) -> Self {
+        Self(Fbm::new(seed.derive("moisture")))
+    }
+
+    /// How wet the ground is, from 0 (arid) to 1 (waterlogged)
+    pub fn moisture_at_xz(&self, x: f32, z: f32) -> f32 {
+        let value = self.0.get([x as f64 / CLIMATE_SCALE, z as f64 / CLIMATE_SCALE]) as f32;
+        (0.5 * value + 0.5).clamp(0.0, 1.0)
+    }
+}
+
+
+#[derive(Resource)]
+pub struct TemperatureNoise(pub Fbm<OpenSimplex>);
+
+impl TemperatureNoise{
+    pub fn new(seed: WorldSeed) -> Self {
+        Self(Fbm::new(seed.derive("temperature")))
+    }
+
+    /// How warm it is, from 0 (freezing) to 1 (scorching), getting colder the higher up `height` is
+    pub fn temperature_at_xz(&self, x: f32, z: f32, height: f32) -> f32 {
+        let value = self.0.get([x as f64 / CLIMATE_SCALE, z as f64 / CLIMATE_SCALE]) as f32;
+        (0.5 * value + 0.5 - TEMPERATURE_LAPSE_RATE * height.max(0.0)).clamp(0.0, 1.0)
     }
+}
+
+/// A reproducible stream of random numbers for one tile and purpose.
+/// The stream only depends on the world seed, the tile's coordinate and the purpose string,
+/// so it comes out the same no matter what order tiles are spawned or regenerated in.
+/// Different purposes (eg "trees", "resources") give independent streams for the same tile
+#[derive(Clone, Debug)]
+pub struct TileRng{
+    state: u64
+}
 
-    pub fn height_at_coord(&self, i: i32, j: i32) -> f32 {
-        self.height_at_xz(x_from_coord(i, j), z_from_coord(i, j))
+impl TileRng{
+    pub fn new(seed: WorldSeed, coord: HexCoord, purpose: &str) -> Self {
+        let mut state = mix(seed.0 as u64 ^ hash_str(purpose));
+        state = mix(state ^ coord.q as u32 as u64);
+        state = mix(state ^ ((coord.r as u32 as u64) << 32));
+        Self {state}
     }
-}
\ No newline at end of file
+
+    /// SplitMix64, small and fast with good enough quality for world generation
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        mix(self.state)
+    }
+
+    #[allow(dead_code)]
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// A float in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        //the top 24 bits fill an f32 mantissa exactly
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+
+    /// A float in [min, max)
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// An integer in [min, max), or `min` if the range is empty
+    #[allow(dead_code)]
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {return min;}
+        let span = (max as i64 - min as i64) as u64;
+        (min as i64 + (self.next_u64() % span) as i64) as i32
+    }
+
+    /// True with probability `chance`
+    #[allow(dead_code)]
+    pub fn chance(&mut self, chance: f32) -> bool {
+        self.next_f32() < chance
+    }
+}
+
+//the SplitMix64 finaliser, spreads every input bit over the whole output
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+//FNV-1a, used instead of the std hashers since those aren't guaranteed to stay the same between releases
+fn hash_str(text: &str) -> u64 {
+    text.bytes().fold(0xCBF29CE484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001B3))
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn stream(seed: u32, coord: HexCoord, purpose: &str) -> Vec<u64> {
+        let mut rng = TileRng::new(WorldSeed(seed), coord, purpose);
+        (0..8).map(|_| rng.next_u64()).collect()
+    }
+
+    #[test]
+    fn same_inputs_give_the_same_stream() {
+        let coord = HexCoord::new(3, -7);
+        assert_eq!(stream(42, coord, "trees"), stream(42, coord, "trees"));
+    }
+
+    #[test]
+    fn stream_does_not_depend_on_other_tiles() {
+        let coord = HexCoord::new(-2, 5);
+        let before = stream(42, coord, "trees");
+        //drawing from other tiles' streams first, as if they were spawned earlier, changes nothing
+        for q in -3..3{
+            stream(42, HexCoord::new(q, 0), "trees");
+        }
+        assert_eq!(before, stream(42, coord, "trees"));
+    }
+
+    #[test]
+    fn different_purposes_give_different_streams() {
+        let coord = HexCoord::new(3, -7);
+        assert_ne!(stream(42, coord, "trees"), stream(42, coord, "resources"));
+    }
+
+    #[test]
+    fn different_tiles_and_seeds_give_different_streams() {
+        let coord = HexCoord::new(3, -7);
+        assert_ne!(stream(42, coord, "trees"), stream(42, HexCoord::new(-7, 3), "trees"));
+        assert_ne!(stream(42, coord, "trees"), stream(43, coord, "trees"));
+    }
+
+    #[test]
+    fn ranges_stay_in_bounds() {
+        let mut rng = TileRng::new(WorldSeed(7), HexCoord::ZERO, "ranges");
+        for _ in 0..1000{
+            let float = rng.range_f32(-2.0, 3.0);
+            assert!((-2.0..3.0).contains(&float));
+            let int = rng.range_i32(-5, 5);
+            assert!((-5..5).contains(&int));
+        }
+        assert_eq!(rng.range_i32(4, 4), 4);
+    }
+}